@@ -0,0 +1,194 @@
+//! Append-only trail of privileged actions.
+//!
+//! Events are inserted through the transaction that makes the change, so a
+//! rolled back change leaves no event behind. With `AUDIT_HASH_CHAIN` every
+//! event also stores `entry_hash = keccak256(prev_hash || content)`, where
+//! `content` is the event's canonical JSON without its id, so editing or
+//! deleting a row breaks every later link.
+
+use actix_web::web::Data;
+use actix_web::{FromRequest, HttpRequest};
+use chrono::{DateTime, SecondsFormat, SubsecRound, Utc};
+use ethers_core::types::H256;
+use ethers_core::utils::keccak256;
+use serde_json::Value;
+use sqlx::{Postgres, Transaction};
+use std::future::{ready, Ready};
+
+use crate::errors::ApiError;
+use crate::middleware::rate_limit::client_ip;
+use crate::middleware::AuthUser;
+use crate::models::AuditEvent;
+use crate::state::AppState;
+
+/// Advisory lock serializing chained inserts, so every event links to the
+/// one committed before it.
+const CHAIN_LOCK: i64 = 0x0061_7564_6974;
+
+/// Where a request came from, as recorded with its audit events.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl FromRequest for ClientInfo {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
+        let trust_proxy_headers = req
+            .app_data::<Data<AppState>>()
+            .is_some_and(|state| state.config.trust_proxy_headers);
+
+        ready(Ok(ClientInfo {
+            ip: Some(client_ip(&req.connection_info(), trust_proxy_headers)),
+            user_agent: req
+                .headers()
+                .get("User-Agent")
+                .and_then(|v| v.to_str().ok())
+                .map(|ua| ua.chars().take(512).collect()),
+        }))
+    }
+}
+
+/// A change about to be recorded: what was done to which row, with the
+/// affected columns before and after.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub action: &'static str,
+    pub target_type: &'static str,
+    pub target_id: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &'static str, target_type: &'static str, target_id: impl ToString) -> Self {
+        Self {
+            action,
+            target_type,
+            target_id: target_id.to_string(),
+            before: None,
+            after: None,
+        }
+    }
+
+    pub fn change(mut self, before: Value, after: Value) -> Self {
+        self.before = Some(before);
+        self.after = Some(after);
+        self
+    }
+
+    /// A newly created row, which has no before state.
+    pub fn created(mut self, after: Value) -> Self {
+        self.after = Some(after);
+        self
+    }
+}
+
+/// Hash of an event linked to `prev`. Covers everything but the id and the
+/// hashes themselves.
+pub fn entry_hash(prev: H256, event: &AuditEvent) -> H256 {
+    let content = serde_json::json!({
+        "actor": event.actor,
+        "actor_type": event.actor_type,
+        "action": event.action,
+        "target_type": event.target_type,
+        "target_id": event.target_id,
+        "before": event.before,
+        "after": event.after,
+        "ip": event.ip,
+        "user_agent": event.user_agent,
+        "created_at": event.created_at.to_rfc3339_opts(SecondsFormat::Micros, true)
+    });
+    let mut data = prev.as_bytes().to_vec();
+    data.extend_from_slice(content.to_string().as_bytes());
+    H256::from(keccak256(data))
+}
+
+/// Walks chained events in id order starting from `prev`. Returns the last
+/// hash, or the id of the first event whose links do not check out.
+pub fn check_chain(mut prev: H256, events: &[AuditEvent]) -> Result<H256, i64> {
+    for event in events {
+        let stored_prev = event.prev_hash.as_deref().and_then(|h| h.parse().ok());
+        let stored_hash = event.entry_hash.as_deref().and_then(|h| h.parse().ok());
+        let hash = entry_hash(prev, event);
+        if stored_prev != Some(prev) || stored_hash != Some(hash) {
+            return Err(event.id);
+        }
+        prev = hash;
+    }
+    Ok(prev)
+}
+
+/// Records `entry` as done by `actor` within `tx`.
+pub async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    state: &AppState,
+    actor: &AuthUser,
+    client: &ClientInfo,
+    entry: AuditEntry,
+) -> Result<AuditEvent, ApiError> {
+    // Postgres keeps microseconds; hash what will be read back.
+    let created_at: DateTime<Utc> = Utc::now().trunc_subsecs(6);
+    let mut event = AuditEvent {
+        id: 0,
+        actor: actor.sub.to_lowercase(),
+        actor_type: actor.auth_type.clone(),
+        action: entry.action.into(),
+        target_type: entry.target_type.into(),
+        target_id: entry.target_id,
+        before: entry.before,
+        after: entry.after,
+        ip: client.ip.clone(),
+        user_agent: client.user_agent.clone(),
+        prev_hash: None,
+        entry_hash: None,
+        created_at,
+    };
+
+    if state.config.audit_hash_chain {
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(CHAIN_LOCK)
+            .execute(&mut **tx)
+            .await?;
+        let last: Option<(String,)> = sqlx::query_as(
+            "SELECT entry_hash FROM audit_events WHERE entry_hash IS NOT NULL ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        let prev = match last {
+            Some((hash,)) => hash.parse().map_err(|_| ApiError::Internal)?,
+            None => H256::zero(),
+        };
+        event.prev_hash = Some(format!("{:#x}", prev));
+        event.entry_hash = Some(format!("{:#x}", entry_hash(prev, &event)));
+    }
+
+    let event: AuditEvent = sqlx::query_as(
+        r#"
+        INSERT INTO audit_events
+            (actor, actor_type, action, target_type, target_id, before, after,
+             ip, user_agent, prev_hash, entry_hash, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING *
+    "#,
+    )
+    .bind(&event.actor)
+    .bind(&event.actor_type)
+    .bind(&event.action)
+    .bind(&event.target_type)
+    .bind(&event.target_id)
+    .bind(&event.before)
+    .bind(&event.after)
+    .bind(&event.ip)
+    .bind(&event.user_agent)
+    .bind(&event.prev_hash)
+    .bind(&event.entry_hash)
+    .bind(event.created_at)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(event)
+}
@@ -0,0 +1,446 @@
+use ethers_core::abi::{self, ParamType, Token};
+use ethers_core::types::transaction::eip2718::TypedTransaction;
+use ethers_core::types::{Address, Bytes, Log, TransactionReceipt, TransactionRequest, H256, U256};
+use ethers_core::utils::{id, keccak256};
+use ethers_providers::{Http, Middleware, Provider};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::config::{redact_url, ChainConfig, Config};
+
+/// Per-endpoint timeout before falling back to the next RPC URL.
+const RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("no contract address configured")]
+    NoContract,
+
+    #[error("RPC request failed: {0}")]
+    Rpc(String),
+
+    #[error("unexpected contract response: {0}")]
+    Decode(String),
+
+    #[error("RPC reports chain id {actual}, expected {expected}")]
+    WrongChain { expected: u64, actual: u64 },
+}
+
+/// `PoolCreated(uint256 indexed poolId, address indexed validator, string name, uint256 feePaid)`
+pub const POOL_CREATED_EVENT: &str = "PoolCreated(uint256,address,string,uint256)";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolCreated {
+    pub pool_id: U256,
+    pub validator: Address,
+}
+
+fn event_topic(signature: &str) -> H256 {
+    H256::from(keccak256(signature.as_bytes()))
+}
+
+/// Logs of `signature` emitted by `contract`.
+fn contract_events<'a>(
+    logs: &'a [Log],
+    contract: Address,
+    signature: &str,
+) -> impl Iterator<Item = &'a Log> {
+    let topic = event_topic(signature);
+    logs.iter()
+        .filter(move |log| log.address == contract && log.topics.first() == Some(&topic))
+}
+
+/// First `PoolCreated` event of `contract` in a transaction's logs.
+pub fn decode_pool_created(logs: &[Log], contract: Address) -> Option<PoolCreated> {
+    contract_events(logs, contract, POOL_CREATED_EVENT).find_map(|log| {
+        match log.topics.as_slice() {
+            [_, pool_id, validator, ..] => Some(PoolCreated {
+                pool_id: U256::from_big_endian(pool_id.as_bytes()),
+                validator: Address::from(*validator),
+            }),
+            _ => None,
+        }
+    })
+}
+
+/// `CertificateRequested(uint256 indexed requestId, address indexed certificator,
+/// address indexed recipient, string certificateHash, string institutionId)`
+pub const CERTIFICATE_REQUESTED_EVENT: &str =
+    "CertificateRequested(uint256,address,address,string,string)";
+
+/// `CertificateMinted(uint256 indexed tokenId, uint256 indexed requestId,
+/// address indexed recipient, string certificateHash)`
+pub const CERTIFICATE_MINTED_EVENT: &str = "CertificateMinted(uint256,uint256,address,string)";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateRequested {
+    pub request_id: U256,
+    pub certificator: Address,
+    pub recipient: Address,
+    pub certificate_hash: String,
+    pub institution_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateMinted {
+    pub token_id: U256,
+    pub request_id: U256,
+    pub recipient: Address,
+    pub certificate_hash: String,
+}
+
+/// ABI-decodes the non-indexed string fields of an event.
+fn decode_strings(data: &[u8], count: usize) -> Option<Vec<String>> {
+    let tokens = abi::decode(&vec![ParamType::String; count], data).ok()?;
+    tokens
+        .into_iter()
+        .map(|token| token.into_string())
+        .collect()
+}
+
+/// First `CertificateRequested` event of `contract` in a transaction's logs.
+pub fn decode_certificate_requested(
+    logs: &[Log],
+    contract: Address,
+) -> Option<CertificateRequested> {
+    contract_events(logs, contract, CERTIFICATE_REQUESTED_EVENT).find_map(|log| {
+        let [_, request_id, certificator, recipient] = log.topics.as_slice() else {
+            return None;
+        };
+        let mut strings = decode_strings(&log.data, 2)?.into_iter();
+        Some(CertificateRequested {
+            request_id: U256::from_big_endian(request_id.as_bytes()),
+            certificator: Address::from(*certificator),
+            recipient: Address::from(*recipient),
+            certificate_hash: strings.next()?,
+            institution_id: strings.next()?,
+        })
+    })
+}
+
+/// First `CertificateMinted` event of `contract` in a transaction's logs.
+pub fn decode_certificate_minted(logs: &[Log], contract: Address) -> Option<CertificateMinted> {
+    contract_events(logs, contract, CERTIFICATE_MINTED_EVENT).find_map(|log| {
+        let [_, token_id, request_id, recipient] = log.topics.as_slice() else {
+            return None;
+        };
+        Some(CertificateMinted {
+            token_id: U256::from_big_endian(token_id.as_bytes()),
+            request_id: U256::from_big_endian(request_id.as_bytes()),
+            recipient: Address::from(*recipient),
+            certificate_hash: decode_strings(&log.data, 1)?.pop()?,
+        })
+    })
+}
+
+/// JSON-RPC access to one configured chain and its contract. Endpoints are
+/// tried in order, so extra URLs act as fallbacks.
+pub struct ChainClient {
+    pub name: String,
+    pub chain_id: u64,
+    providers: Vec<(String, Provider<Http>)>,
+    pub contract_address: Option<Address>,
+}
+
+impl ChainClient {
+    pub fn new(chain: &ChainConfig) -> Result<Self, ChainError> {
+        let providers = chain
+            .rpc_urls
+            .iter()
+            .map(|url| {
+                Provider::<Http>::try_from(url.as_str())
+                    .map(|provider| (redact_url(url), provider))
+                    .map_err(|e| ChainError::Rpc(e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let contract_address = chain
+            .contract_address
+            .as_deref()
+            .map(|address| address.parse::<Address>())
+            .transpose()
+            .map_err(|e| ChainError::Decode(e.to_string()))?;
+
+        Ok(Self {
+            name: chain.name.clone(),
+            chain_id: chain.chain_id,
+            providers,
+            contract_address,
+        })
+    }
+
+    /// Runs `request` against each endpoint until one succeeds.
+    async fn with_fallback<T, F, Fut>(&self, request: F) -> Result<T, ChainError>
+    where
+        F: Fn(Provider<Http>) -> Fut,
+        Fut: std::future::Future<Output = Result<T, String>>,
+    {
+        let mut last_error =
+            ChainError::Rpc(format!("no RPC endpoint configured for {}", self.name));
+
+        for (name, provider) in &self.providers {
+            match tokio::time::timeout(RPC_TIMEOUT, request(provider.clone())).await {
+                Ok(Ok(value)) => return Ok(value),
+                Ok(Err(e)) => {
+                    tracing::warn!(rpc = %name, error = %e, "RPC request failed");
+                    last_error = ChainError::Rpc(e);
+                }
+                Err(_) => {
+                    tracing::warn!(rpc = %name, "RPC request timed out");
+                    last_error = ChainError::Rpc(format!("{} timed out", name));
+                }
+            }
+        }
+
+        Err(last_error)
+    }
+
+    pub async fn rpc_chain_id(&self) -> Result<u64, ChainError> {
+        self.with_fallback(|provider| async move {
+            provider
+                .get_chainid()
+                .await
+                .map(|id| id.as_u64())
+                .map_err(|e| e.to_string())
+        })
+        .await
+    }
+
+    /// `eth_call` of a view function on the contract.
+    async fn call_view(&self, signature: &str, args: &[Token]) -> Result<Bytes, ChainError> {
+        let contract = self.contract_address.ok_or(ChainError::NoContract)?;
+        let mut data = id(signature).to_vec();
+        data.extend(abi::encode(args));
+        let tx: TypedTransaction = TransactionRequest::new().to(contract).data(data).into();
+
+        self.with_fallback(|provider| {
+            let tx = tx.clone();
+            async move { provider.call(&tx, None).await.map_err(|e| e.to_string()) }
+        })
+        .await
+    }
+
+    /// Receipt of a mined transaction, `None` while it is unknown or pending.
+    pub async fn receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>, ChainError> {
+        self.with_fallback(|provider| async move {
+            provider
+                .get_transaction_receipt(tx_hash)
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await
+    }
+
+    /// Logs of a successfully mined transaction together with the contract
+    /// address to match them against. `None` while pending or when reverted.
+    async fn mined_logs(&self, tx_hash: H256) -> Result<Option<(Vec<Log>, Address)>, ChainError> {
+        let contract = self.contract_address.ok_or(ChainError::NoContract)?;
+        let Some(receipt) = self.receipt(tx_hash).await? else {
+            return Ok(None);
+        };
+        if receipt.status != Some(1.into()) {
+            return Ok(None);
+        }
+        Ok(Some((receipt.logs, contract)))
+    }
+
+    /// `PoolCreated` event emitted by the contract in a successful transaction.
+    pub async fn pool_created(&self, tx_hash: H256) -> Result<Option<PoolCreated>, ChainError> {
+        Ok(self
+            .mined_logs(tx_hash)
+            .await?
+            .and_then(|(logs, contract)| decode_pool_created(&logs, contract)))
+    }
+
+    /// `CertificateRequested` event emitted by the contract in a successful
+    /// transaction.
+    pub async fn certificate_requested(
+        &self,
+        tx_hash: H256,
+    ) -> Result<Option<CertificateRequested>, ChainError> {
+        Ok(self
+            .mined_logs(tx_hash)
+            .await?
+            .and_then(|(logs, contract)| decode_certificate_requested(&logs, contract)))
+    }
+
+    /// `CertificateMinted` event emitted by the contract in a successful
+    /// transaction.
+    pub async fn certificate_minted(
+        &self,
+        tx_hash: H256,
+    ) -> Result<Option<CertificateMinted>, ChainError> {
+        Ok(self
+            .mined_logs(tx_hash)
+            .await?
+            .and_then(|(logs, contract)| decode_certificate_minted(&logs, contract)))
+    }
+
+    pub async fn total_pools(&self) -> Result<U256, ChainError> {
+        let output = self.call_view("totalPools()", &[]).await?;
+        decode_uint(&output)
+    }
+
+    /// Minted certificate registered under `certificate_hash` on the contract.
+    pub async fn certificate_by_hash(
+        &self,
+        certificate_hash: &str,
+    ) -> Result<Option<OnChainCertificate>, ChainError> {
+        let output = self
+            .call_view(
+                VERIFY_CERTIFICATE_BY_HASH,
+                &[Token::String(certificate_hash.to_string())],
+            )
+            .await?;
+        decode_certificate_by_hash(&output)
+    }
+
+    /// Pool `pool_id` as registered on the contract.
+    pub async fn pool(&self, pool_id: U256) -> Result<OnChainPool, ChainError> {
+        let output = self.call_view(GET_POOL, &[Token::Uint(pool_id)]).await?;
+        decode_pool(&output)
+    }
+
+    /// Checks the RPC is on the configured chain and the contract answers
+    /// `totalPools()`, returning the pool count.
+    pub async fn verify_deployment(&self) -> Result<U256, ChainError> {
+        let actual = self.rpc_chain_id().await?;
+        if actual != self.chain_id {
+            return Err(ChainError::WrongChain {
+                expected: self.chain_id,
+                actual,
+            });
+        }
+
+        self.total_pools().await
+    }
+}
+
+/// Return value of `verifyCertificateByHash` for a minted certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnChainCertificate {
+    pub token_id: U256,
+    pub recipient: Address,
+    pub institution_id: String,
+    pub minted_at: U256,
+}
+
+pub const VERIFY_CERTIFICATE_BY_HASH: &str = "verifyCertificateByHash(string)";
+
+/// Decodes `verifyCertificateByHash`, `None` when the contract reports the
+/// hash as unknown.
+pub fn decode_certificate_by_hash(output: &[u8]) -> Result<Option<OnChainCertificate>, ChainError> {
+    let tokens = abi::decode(
+        &[
+            ParamType::Bool,
+            ParamType::Uint(256),
+            ParamType::Address,
+            ParamType::String,
+            ParamType::Uint(256),
+        ],
+        output,
+    )
+    .map_err(|e| ChainError::Decode(e.to_string()))?;
+
+    let malformed = || ChainError::Decode("malformed verifyCertificateByHash output".into());
+    let mut tokens = tokens.into_iter();
+    let mut next = || tokens.next().ok_or_else(malformed);
+    let is_valid = next()?.into_bool().ok_or_else(malformed)?;
+    let token_id = next()?.into_uint().ok_or_else(malformed)?;
+    let recipient = next()?.into_address().ok_or_else(malformed)?;
+    let institution_id = next()?.into_string().ok_or_else(malformed)?;
+    let minted_at = next()?.into_uint().ok_or_else(malformed)?;
+
+    Ok(is_valid.then_some(OnChainCertificate {
+        token_id,
+        recipient,
+        institution_id,
+        minted_at,
+    }))
+}
+
+/// Return value of `getPool` for an existing pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnChainPool {
+    pub pool_id: U256,
+    pub validator: Address,
+    pub institution_id: String,
+    pub is_active: bool,
+}
+
+pub const GET_POOL: &str = "getPool(uint256)";
+
+/// Decodes the `Pool` struct returned by `getPool`.
+pub fn decode_pool(output: &[u8]) -> Result<OnChainPool, ChainError> {
+    let pool = ParamType::Tuple(vec![
+        ParamType::Uint(256),
+        ParamType::String,
+        ParamType::String,
+        ParamType::Address,
+        ParamType::String,
+        ParamType::Bool,
+        ParamType::Uint(256),
+    ]);
+    let tokens = abi::decode(&[pool], output).map_err(|e| ChainError::Decode(e.to_string()))?;
+
+    let malformed = || ChainError::Decode("malformed getPool output".into());
+    let fields = tokens
+        .into_iter()
+        .next()
+        .and_then(|token| token.into_tuple())
+        .ok_or_else(malformed)?;
+    let mut fields = fields.into_iter();
+    let mut next = || fields.next().ok_or_else(malformed);
+    let pool_id = next()?.into_uint().ok_or_else(malformed)?;
+    let _name = next()?;
+    let _description = next()?;
+    let validator = next()?.into_address().ok_or_else(malformed)?;
+    let institution_id = next()?.into_string().ok_or_else(malformed)?;
+    let is_active = next()?.into_bool().ok_or_else(malformed)?;
+
+    Ok(OnChainPool {
+        pool_id,
+        validator,
+        institution_id,
+        is_active,
+    })
+}
+
+/// Clients for every configured chain that has RPC endpoints, keyed by chain id.
+#[derive(Clone, Default)]
+pub struct Chains {
+    clients: HashMap<u64, Arc<ChainClient>>,
+}
+
+impl Chains {
+    pub fn from_config(config: &Config) -> Result<Self, ChainError> {
+        let mut clients = HashMap::new();
+        for chain in config.chains.iter().filter(|c| !c.rpc_urls.is_empty()) {
+            clients.insert(chain.chain_id, Arc::new(ChainClient::new(chain)?));
+        }
+        Ok(Self { clients })
+    }
+
+    pub fn get(&self, chain_id: u64) -> Option<&Arc<ChainClient>> {
+        self.clients.get(&chain_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<ChainClient>> {
+        let mut clients: Vec<_> = self.clients.values().collect();
+        clients.sort_by_key(|client| client.chain_id);
+        clients.into_iter()
+    }
+}
+
+/// Decodes a single ABI-encoded `uint256` return value.
+pub fn decode_uint(output: &[u8]) -> Result<U256, ChainError> {
+    if output.len() != 32 {
+        return Err(ChainError::Decode(format!(
+            "expected 32 bytes, got {}",
+            output.len()
+        )));
+    }
+    Ok(U256::from_big_endian(output))
+}
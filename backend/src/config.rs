@@ -1,29 +1,676 @@
+use ethers_core::types::U256;
+use ethers_core::utils::{format_units, parse_ether};
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub const DEV_JWT_SECRET: &str = "etched-dev-secret-change-in-production";
+const ZERO_ADDRESS: &str = "0x0000000000000000000000000000000000000000";
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const MIN_PRODUCTION_SECRET_LEN: usize = 32;
+const DEV_ADMIN_EMAIL: &str = "admin@admin.com";
+const DEV_ADMIN_PASSWORD: &str = "admin123";
+const MIN_PRODUCTION_ADMIN_PASSWORD_LEN: usize = 12;
+
+/// Every problem found while loading the config, reported together so a broken
+/// deployment can be fixed in one go.
+#[derive(Debug, Error)]
+#[error("invalid configuration:\n  - {}", .0.join("\n  - "))]
+pub struct ConfigError(pub Vec<String>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Development,
+    Production,
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "development" | "dev" => Ok(Profile::Development),
+            "production" | "prod" => Ok(Profile::Production),
+            other => Err(format!("expected development or production, got {}", other)),
+        }
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Profile::Development => write!(f, "development"),
+            Profile::Production => write!(f, "production"),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
+    pub profile: Profile,
     pub database_url: String,
     pub jwt_secret: String,
     pub admin_wallet: String,
+    /// Credentials of the admin account created on an empty database. Only
+    /// development falls back to a default account.
+    pub bootstrap_admin_email: Option<String>,
+    pub bootstrap_admin_password: Option<String>,
     pub bind_addr: String,
-    pub pool_cost_eth: f64,
+    pub pool_cost_wei: U256,
+    pub public_base_url: String,
+    pub frontend_url: String,
+    pub mail_backend: String,
+    pub mail_from: String,
+    pub mail_dir: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub trust_proxy_headers: bool,
+    pub rate_limit_window_secs: u64,
+    pub rate_limit_ip_max: u32,
+    pub rate_limit_key_max: u32,
+    pub lockout_threshold: i32,
+    pub lockout_base_secs: i64,
+    pub lockout_max_secs: i64,
+    pub log_format: String,
+    pub metrics_token: Option<String>,
+    /// Hex secp256k1 key that signs verification receipts; receipts are
+    /// disabled when unset.
+    pub receipt_signing_key: Option<String>,
+    /// Link every audit event to the previous one by hash.
+    pub audit_hash_chain: bool,
+    pub chains: Vec<ChainConfig>,
+    pub default_chain_id: Option<u64>,
+    pub cors_allowed_origins: Vec<String>,
+    pub max_json_body_bytes: usize,
+    pub hsts_enabled: bool,
+    pub hsts_max_age_secs: u64,
+    pub content_type_nosniff: bool,
+    pub frame_deny: bool,
+}
+
+/// One chain the backend issues on. The unprefixed keys (`RPC_URLS`,
+/// `CHAIN_ID`, ...) describe the default chain; each name listed in `CHAINS`
+/// adds another from `CHAIN_<NAME>_RPC_URLS`, `CHAIN_<NAME>_CHAIN_ID`, ...
+#[derive(Debug, Clone)]
+pub struct ChainConfig {
+    pub name: String,
+    pub chain_id: u64,
+    pub rpc_urls: Vec<String>,
+    pub contract_address: Option<String>,
+    pub deployment_block: Option<u64>,
+}
+
+/// Contract deployment record written by `contracts/scripts/deploy.js`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub address: String,
+    pub network: Option<String>,
+    pub chain_id: Option<u64>,
+    pub deployment_block: Option<u64>,
+}
+
+/// Config values from the TOML file, overridden by environment variables.
+/// File keys are the lowercase env names; nested tables are joined with `_`,
+/// so `[smtp] host = ...` is the same as `SMTP_HOST`.
+struct Source {
+    file: HashMap<String, String>,
+    errors: Vec<String>,
+}
+
+fn flatten_toml(prefix: &str, table: &toml::Table, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.to_lowercase()
+        } else {
+            format!("{}_{}", prefix, key.to_lowercase())
+        };
+        match value {
+            toml::Value::Table(nested) => flatten_toml(&key, nested, out),
+            toml::Value::String(s) => {
+                out.insert(key, s.clone());
+            }
+            toml::Value::Array(items) => {
+                let joined = items
+                    .iter()
+                    .map(|item| match item {
+                        toml::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                out.insert(key, joined);
+            }
+            other => {
+                out.insert(key, other.to_string());
+            }
+        }
+    }
+}
+
+impl Source {
+    fn new(file_contents: Option<&str>) -> Self {
+        let mut source = Source {
+            file: HashMap::new(),
+            errors: Vec::new(),
+        };
+
+        if let Some(contents) = file_contents {
+            match contents.parse::<toml::Table>() {
+                Ok(table) => flatten_toml("", &table, &mut source.file),
+                Err(e) => source.errors.push(format!("config file: {}", e)),
+            }
+        }
+
+        source
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        env::var(key)
+            .ok()
+            .or_else(|| self.file.get(&key.to_lowercase()).cloned())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    fn in_env(&self, key: &str) -> bool {
+        env::var(key).is_ok_and(|v| !v.trim().is_empty())
+    }
+
+    fn string(&self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+
+    fn required(&mut self, key: &str) -> String {
+        self.get(key).unwrap_or_else(|| {
+            self.errors.push(format!("{} must be set", key));
+            String::new()
+        })
+    }
+
+    /// Parses `key`, recording an error instead of silently falling back to the
+    /// default when the value is present but malformed.
+    fn parse<T: FromStr>(&mut self, key: &str, default: T) -> T
+    where
+        T::Err: Display,
+    {
+        self.parse_optional(key).unwrap_or(default)
+    }
+
+    fn parse_optional<T: FromStr>(&mut self, key: &str) -> Option<T>
+    where
+        T::Err: Display,
+    {
+        let raw = self.get(key)?;
+        match raw.parse() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.errors
+                    .push(format!("{}: invalid value {:?} ({})", key, raw, e));
+                None
+            }
+        }
+    }
+}
+
+pub fn is_valid_address(address: &str) -> bool {
+    address.len() == 42
+        && address.starts_with("0x")
+        && address[2..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Keeps only scheme and host, since RPC URLs often embed API keys.
+pub fn redact_url(value: &str) -> String {
+    url::Url::parse(value)
+        .ok()
+        .and_then(|url| Some(format!("{}://{}", url.scheme(), url.host_str()?)))
+        .unwrap_or_else(|| "[redacted]".into())
+}
+
+fn is_valid_http_url(value: &str) -> bool {
+    url::Url::parse(value)
+        .map(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+        .unwrap_or(false)
+}
+
+/// Wei amount as a decimal ETH string without trailing zeros (`0.1`, `2`).
+pub fn format_eth(wei: U256) -> String {
+    let formatted = format_units(wei, "ether").unwrap_or_else(|_| wei.to_string());
+    if formatted.contains('.') {
+        formatted
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    } else {
+        formatted
+    }
+}
+
+fn read_deployment(path: &str) -> Result<Deployment, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&contents).map_err(|e| e.to_string())
+}
+
+/// Reads the chain whose keys start with `prefix`, filling settings it does
+/// not set from its deployment file. Explicit settings win, but must not
+/// contradict the file. `None` when nothing is configured for the prefix.
+fn load_chain(src: &mut Source, prefix: &str, name: Option<String>) -> Option<ChainConfig> {
+    let key = |suffix: &str| format!("{}{}", prefix, suffix);
+
+    let mut rpc_urls: Vec<String> = src
+        .get(&key("RPC_URLS"))
+        .or_else(|| src.get(&key("RPC_URL")))
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+    let mut chain_id: Option<u64> = src.parse_optional(&key("CHAIN_ID"));
+    let mut contract_address = src.get(&key("CONTRACT_ADDRESS")).map(|a| a.to_lowercase());
+    let mut deployment_block: Option<u64> = src.parse_optional(&key("DEPLOYMENT_BLOCK"));
+    let mut name = name.or_else(|| src.get(&key("NETWORK")));
+    let deployment_file = src.get(&key("DEPLOYMENT_FILE"));
+
+    if rpc_urls.is_empty()
+        && chain_id.is_none()
+        && contract_address.is_none()
+        && deployment_file.is_none()
+    {
+        return None;
+    }
+
+    if let Some(path) = deployment_file {
+        match read_deployment(&path) {
+            Ok(deployment) => {
+                let address = deployment.address.to_lowercase();
+                match &contract_address {
+                    Some(configured) if *configured != address => src.errors.push(format!(
+                        "{} {} does not match DEPLOYMENT_FILE address {}",
+                        key("CONTRACT_ADDRESS"),
+                        configured,
+                        address
+                    )),
+                    Some(_) => {}
+                    None => contract_address = Some(address),
+                }
+                match (chain_id, deployment.chain_id) {
+                    (Some(configured), Some(deployed)) if configured != deployed => {
+                        src.errors.push(format!(
+                            "{} {} does not match DEPLOYMENT_FILE chainId {}",
+                            key("CHAIN_ID"),
+                            configured,
+                            deployed
+                        ))
+                    }
+                    (None, deployed) => chain_id = deployed,
+                    _ => {}
+                }
+                name = name.or(deployment.network);
+                deployment_block = deployment_block.or(deployment.deployment_block);
+            }
+            Err(e) => src
+                .errors
+                .push(format!("{} {}: {}", key("DEPLOYMENT_FILE"), path, e)),
+        }
+    }
+
+    rpc_urls.retain(|url| {
+        let valid = is_valid_http_url(url);
+        if !valid {
+            src.errors.push(format!(
+                "{}: {:?} is not an http(s) URL",
+                key("RPC_URLS"),
+                redact_url(url)
+            ));
+        }
+        valid
+    });
+    if let Some(address) = &contract_address {
+        if !is_valid_address(address) {
+            src.errors.push(format!(
+                "{}: {:?} is not a 0x-prefixed 20-byte address",
+                key("CONTRACT_ADDRESS"),
+                address
+            ));
+        }
+    }
+
+    let Some(chain_id) = chain_id else {
+        src.errors.push(format!(
+            "{} must be set when the chain has an RPC URL or contract",
+            key("CHAIN_ID")
+        ));
+        return None;
+    };
+
+    Some(ChainConfig {
+        name: name.unwrap_or_else(|| format!("chain-{}", chain_id)),
+        chain_id,
+        rpc_urls,
+        contract_address,
+        deployment_block,
+    })
+}
+
+/// The default chain from the unprefixed keys, then every chain named in
+/// `CHAINS`.
+fn load_chains(src: &mut Source) -> Vec<ChainConfig> {
+    let mut chains: Vec<ChainConfig> = load_chain(src, "", None).into_iter().collect();
+
+    let names = src.get("CHAINS").unwrap_or_default();
+    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let prefix = format!("CHAIN_{}_", name.to_uppercase().replace('-', "_"));
+        match load_chain(src, &prefix, Some(name.to_lowercase())) {
+            Some(chain) => chains.push(chain),
+            None => src
+                .errors
+                .push(format!("CHAINS: no settings found for chain {}", name)),
+        }
+    }
+
+    chains
 }
 
 impl Config {
-    pub fn from_env() -> Self {
+    /// Loads `CONFIG_FILE` (default `config.toml`, optional) and applies
+    /// environment overrides on top.
+    pub fn load() -> Result<Self, ConfigError> {
         dotenvy::dotenv().ok();
 
-        Self {
-            database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
-            jwt_secret: env::var("JWT_SECRET")
-                .unwrap_or_else(|_| "etched-dev-secret-change-in-production".into()),
-            admin_wallet: env::var("ADMIN_WALLET")
-                .unwrap_or_else(|_| "0x0000000000000000000000000000000000000000".into()),
-            bind_addr: env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".into()),
-            pool_cost_eth: env::var("POOL_COST_ETH")
-                .unwrap_or_else(|_| "0.1".into())
-                .parse()
-                .unwrap_or(0.1),
+        let explicit = env::var("CONFIG_FILE").ok();
+        let path = explicit.as_deref().unwrap_or(DEFAULT_CONFIG_FILE);
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => Some(contents),
+            Err(_) if explicit.is_none() => None,
+            Err(e) => return Err(ConfigError(vec![format!("CONFIG_FILE {}: {}", path, e)])),
+        };
+
+        Self::from_sources(contents.as_deref())
+    }
+
+    /// Builds the config from environment variables layered over the given
+    /// TOML file contents.
+    pub fn from_sources(file_contents: Option<&str>) -> Result<Self, ConfigError> {
+        let mut src = Source::new(file_contents);
+
+        // Either spelling in the environment beats either spelling in the file.
+        let use_wei = if src.in_env("POOL_COST_WEI") || src.in_env("POOL_COST_ETH") {
+            src.in_env("POOL_COST_WEI")
+        } else {
+            src.get("POOL_COST_WEI").is_some()
+        };
+        let pool_cost_wei = if use_wei {
+            src.parse_optional::<U256Dec>("POOL_COST_WEI").map(|v| v.0)
+        } else {
+            let eth = src.string("POOL_COST_ETH", "0.1");
+            match parse_ether(&eth) {
+                Ok(wei) => Some(wei),
+                Err(e) => {
+                    src.errors
+                        .push(format!("POOL_COST_ETH: invalid value {:?} ({})", eth, e));
+                    None
+                }
+            }
+        }
+        .unwrap_or_default();
+
+        let mut config = Self {
+            profile: src.parse("APP_ENV", Profile::Development),
+            database_url: src.required("DATABASE_URL"),
+            jwt_secret: src.string("JWT_SECRET", DEV_JWT_SECRET),
+            admin_wallet: src.string("ADMIN_WALLET", ZERO_ADDRESS).to_lowercase(),
+            bootstrap_admin_email: src.get("BOOTSTRAP_ADMIN_EMAIL"),
+            bootstrap_admin_password: src.get("BOOTSTRAP_ADMIN_PASSWORD"),
+            bind_addr: src.string("BIND_ADDR", "0.0.0.0:8080"),
+            pool_cost_wei,
+            public_base_url: src
+                .string("PUBLIC_BASE_URL", "http://localhost:8080")
+                .trim_end_matches('/')
+                .to_string(),
+            frontend_url: src
+                .string("FRONTEND_URL", "http://localhost:3000")
+                .trim_end_matches('/')
+                .to_string(),
+            mail_backend: src.string("MAIL_BACKEND", "file"),
+            mail_from: src.string("MAIL_FROM", "Etched <no-reply@etched.local>"),
+            mail_dir: src.get("MAIL_DIR"),
+            smtp_host: src.get("SMTP_HOST"),
+            smtp_port: src.parse("SMTP_PORT", 587),
+            smtp_username: src.get("SMTP_USERNAME"),
+            smtp_password: src.get("SMTP_PASSWORD"),
+            trust_proxy_headers: src.parse("TRUST_PROXY_HEADERS", false),
+            rate_limit_window_secs: src.parse("RATE_LIMIT_WINDOW_SECS", 60),
+            rate_limit_ip_max: src.parse("RATE_LIMIT_IP_MAX", 30),
+            rate_limit_key_max: src.parse("RATE_LIMIT_KEY_MAX", 10),
+            lockout_threshold: src.parse("LOCKOUT_THRESHOLD", 5),
+            lockout_base_secs: src.parse("LOCKOUT_BASE_SECS", 60),
+            lockout_max_secs: src.parse("LOCKOUT_MAX_SECS", 60 * 60),
+            log_format: src.string("LOG_FORMAT", "pretty"),
+            metrics_token: src.get("METRICS_TOKEN"),
+            receipt_signing_key: src.get("RECEIPT_SIGNING_KEY"),
+            audit_hash_chain: src.parse("AUDIT_HASH_CHAIN", false),
+            chains: Vec::new(),
+            default_chain_id: None,
+            cors_allowed_origins: src
+                .string("CORS_ALLOWED_ORIGINS", "http://localhost:3000")
+                .split(',')
+                .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect(),
+            max_json_body_bytes: src.parse("MAX_JSON_BODY_BYTES", 64 * 1024),
+            hsts_enabled: src.parse("HSTS_ENABLED", false),
+            hsts_max_age_secs: src.parse("HSTS_MAX_AGE_SECS", 365 * 24 * 60 * 60),
+            content_type_nosniff: src.parse("CONTENT_TYPE_NOSNIFF", true),
+            frame_deny: src.parse("FRAME_DENY", true),
+        };
+
+        config.chains = load_chains(&mut src);
+        config.default_chain_id = src
+            .parse_optional("DEFAULT_CHAIN_ID")
+            .or_else(|| config.chains.first().map(|chain| chain.chain_id));
+
+        let mut errors = src.errors;
+        errors.extend(config.validate());
+
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(errors))
+        }
+    }
+
+    pub fn chain(&self, chain_id: u64) -> Option<&ChainConfig> {
+        self.chains.iter().find(|chain| chain.chain_id == chain_id)
+    }
+
+    pub fn default_chain(&self) -> Option<&ChainConfig> {
+        self.default_chain_id.and_then(|id| self.chain(id))
+    }
+
+    /// Admin account to create when none exists yet.
+    pub fn bootstrap_admin(&self) -> Option<(&str, &str)> {
+        match (&self.bootstrap_admin_email, &self.bootstrap_admin_password) {
+            (Some(email), Some(password)) => Some((email, password)),
+            _ if self.profile == Profile::Development => {
+                Some((DEV_ADMIN_EMAIL, DEV_ADMIN_PASSWORD))
+            }
+            _ => None,
+        }
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !is_valid_address(&self.admin_wallet) {
+            errors.push(format!(
+                "ADMIN_WALLET: {:?} is not a 0x-prefixed 20-byte address",
+                self.admin_wallet
+            ));
+        }
+        for (key, value) in [
+            ("PUBLIC_BASE_URL", &self.public_base_url),
+            ("FRONTEND_URL", &self.frontend_url),
+        ] {
+            if !is_valid_http_url(value) {
+                errors.push(format!("{}: {:?} is not an http(s) URL", key, value));
+            }
+        }
+        if self.bootstrap_admin_email.is_some() != self.bootstrap_admin_password.is_some() {
+            errors.push(
+                "BOOTSTRAP_ADMIN_EMAIL and BOOTSTRAP_ADMIN_PASSWORD must be set together".into(),
+            );
+        }
+        if let Some(default) = self.default_chain_id {
+            if !self.chains.is_empty() && self.chain(default).is_none() {
+                errors.push(format!(
+                    "DEFAULT_CHAIN_ID: chain {} is not configured",
+                    default
+                ));
+            }
+        }
+        let mut seen = std::collections::HashSet::new();
+        for chain in &self.chains {
+            if !seen.insert(chain.chain_id) {
+                errors.push(format!(
+                    "chain id {} is configured more than once",
+                    chain.chain_id
+                ));
+            }
+        }
+        for origin in &self.cors_allowed_origins {
+            if origin != "*" && !is_valid_http_url(origin) {
+                errors.push(format!(
+                    "CORS_ALLOWED_ORIGINS: {:?} is not an http(s) origin",
+                    origin
+                ));
+            }
+        }
+        match self.mail_backend.as_str() {
+            "file" => {}
+            "smtp" if self.smtp_host.is_none() => {
+                errors.push("SMTP_HOST must be set when MAIL_BACKEND=smtp".into())
+            }
+            "smtp" => {}
+            other => errors.push(format!(
+                "MAIL_BACKEND: expected file or smtp, got {}",
+                other
+            )),
+        }
+        if let Some(key) = &self.receipt_signing_key {
+            if crate::receipt::ReceiptSigner::from_hex(key).is_err() {
+                errors.push("RECEIPT_SIGNING_KEY must be a hex secp256k1 private key".into());
+            }
+        }
+        if !matches!(self.log_format.as_str(), "pretty" | "json") {
+            errors.push(format!(
+                "LOG_FORMAT: expected pretty or json, got {}",
+                self.log_format
+            ));
+        }
+
+        if self.profile == Profile::Production {
+            if self.jwt_secret == DEV_JWT_SECRET
+                || self.jwt_secret.len() < MIN_PRODUCTION_SECRET_LEN
+            {
+                errors.push(format!(
+                    "JWT_SECRET must be a random value of at least {} characters in production",
+                    MIN_PRODUCTION_SECRET_LEN
+                ));
+            }
+            if self.admin_wallet == ZERO_ADDRESS {
+                errors.push("ADMIN_WALLET must be set in production".into());
+            }
+            if self.mail_backend != "smtp" {
+                errors.push("MAIL_BACKEND must be smtp in production".into());
+            }
+            if let Some(password) = &self.bootstrap_admin_password {
+                if password == DEV_ADMIN_PASSWORD
+                    || password.len() < MIN_PRODUCTION_ADMIN_PASSWORD_LEN
+                {
+                    errors.push(format!(
+                        "BOOTSTRAP_ADMIN_PASSWORD must be at least {} characters in production",
+                        MIN_PRODUCTION_ADMIN_PASSWORD_LEN
+                    ));
+                }
+            }
         }
+
+        errors
+    }
+
+    /// Effective config for startup logs, with secrets and credentials removed.
+    pub fn redacted(&self) -> serde_json::Value {
+        let secret = |value: Option<&String>| value.map(|_| "[redacted]");
+        let database_url = url::Url::parse(&self.database_url)
+            .map(|mut url| {
+                if url.password().is_some() {
+                    let _ = url.set_password(Some("redacted"));
+                }
+                url.to_string()
+            })
+            .unwrap_or_else(|_| "[redacted]".into());
+
+        serde_json::json!({
+            "profile": self.profile.to_string(),
+            "database_url": database_url,
+            "jwt_secret": secret(Some(&self.jwt_secret)),
+            "admin_wallet": self.admin_wallet,
+            "bootstrap_admin_email": self.bootstrap_admin_email,
+            "bootstrap_admin_password": secret(self.bootstrap_admin_password.as_ref()),
+            "bind_addr": self.bind_addr,
+            "pool_cost_wei": self.pool_cost_wei.to_string(),
+            "public_base_url": self.public_base_url,
+            "frontend_url": self.frontend_url,
+            "mail_backend": self.mail_backend,
+            "mail_from": self.mail_from,
+            "mail_dir": self.mail_dir,
+            "smtp_host": self.smtp_host,
+            "smtp_port": self.smtp_port,
+            "smtp_username": self.smtp_username,
+            "smtp_password": secret(self.smtp_password.as_ref()),
+            "trust_proxy_headers": self.trust_proxy_headers,
+            "rate_limit_window_secs": self.rate_limit_window_secs,
+            "rate_limit_ip_max": self.rate_limit_ip_max,
+            "rate_limit_key_max": self.rate_limit_key_max,
+            "lockout_threshold": self.lockout_threshold,
+            "lockout_base_secs": self.lockout_base_secs,
+            "lockout_max_secs": self.lockout_max_secs,
+            "log_format": self.log_format,
+            "metrics_token": secret(self.metrics_token.as_ref()),
+            "receipt_signing_key": secret(self.receipt_signing_key.as_ref()),
+            "audit_hash_chain": self.audit_hash_chain,
+            "chains": self.chains.iter().map(|chain| serde_json::json!({
+                "name": chain.name,
+                "chain_id": chain.chain_id,
+                "rpc_urls": chain.rpc_urls.iter().map(|url| redact_url(url)).collect::<Vec<_>>(),
+                "contract_address": chain.contract_address,
+                "deployment_block": chain.deployment_block
+            })).collect::<Vec<_>>(),
+            "default_chain_id": self.default_chain_id,
+            "cors_allowed_origins": self.cors_allowed_origins,
+            "max_json_body_bytes": self.max_json_body_bytes,
+            "hsts_enabled": self.hsts_enabled,
+            "hsts_max_age_secs": self.hsts_max_age_secs,
+            "content_type_nosniff": self.content_type_nosniff,
+            "frame_deny": self.frame_deny
+        })
+    }
+}
+
+/// `U256` parsed from a decimal string (`U256::from_str` expects hex).
+struct U256Dec(U256);
+
+impl FromStr for U256Dec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        U256::from_dec_str(s)
+            .map(U256Dec)
+            .map_err(|e| e.to_string())
     }
 }
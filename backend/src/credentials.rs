@@ -0,0 +1,259 @@
+//! W3C Verifiable Credentials for minted certificates, issued as JWT-VCs
+//! signed with ES256K by the backend's key.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use ethers_core::k256::ecdsa::signature::Verifier;
+use ethers_core::k256::ecdsa::{Signature, VerifyingKey};
+use ethers_core::types::Address;
+use thiserror::Error;
+
+use crate::models::{Certificate, Institution, Pool};
+use crate::receipt::ReceiptSigner;
+
+pub const CREDENTIALS_CONTEXT: &str = "https://www.w3.org/2018/credentials/v1";
+pub const CREDENTIAL_TYPE: &str = "EtchedCertificateCredential";
+pub const CREDENTIALS_V2_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+pub const OPEN_BADGES_CONTEXT: &str = "https://purl.imsglobal.org/spec/ob/v3p0/context-3.0.3.json";
+
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    #[error("malformed JWT")]
+    Malformed,
+
+    #[error("unsupported JWT algorithm {0}")]
+    UnsupportedAlgorithm(String),
+
+    #[error("signature does not match the issuing key")]
+    BadSignature,
+}
+
+/// DID of the key that signs credentials, used as the JWT `kid`.
+pub fn signer_did(address: Address) -> String {
+    format!("did:ethr:{:#x}", address)
+}
+
+/// Public profile URL of an institution.
+pub fn institution_profile_url(public_base_url: &str, institution: &Institution) -> String {
+    format!(
+        "{}/institutions/{}",
+        public_base_url, institution.institution_id
+    )
+}
+
+/// JWT-VC claims for a minted certificate. The issuer is the DID of the
+/// signing key `issuer`, named after the institution; the holder is
+/// identified by their wallet as a `did:pkh` on the pool's chain.
+pub fn certificate_credential(
+    public_base_url: &str,
+    issuer: Address,
+    cert: &Certificate,
+    pool: &Pool,
+    institution: &Institution,
+    issued_at: DateTime<Utc>,
+) -> serde_json::Value {
+    let issuer_id = signer_did(issuer);
+    let minted_at = cert.minted_at.unwrap_or(issued_at);
+    let subject_id = format!(
+        "did:pkh:eip155:{}:{}",
+        pool.chain_id.unwrap_or(1),
+        cert.recipient_wallet.to_lowercase()
+    );
+    let credential_id = format!("{}/certificates/{}/vc", public_base_url, cert.id);
+
+    serde_json::json!({
+        "iss": issuer_id,
+        "sub": subject_id,
+        "jti": credential_id,
+        "nbf": minted_at.timestamp(),
+        "iat": issued_at.timestamp(),
+        "vc": {
+            "@context": [CREDENTIALS_CONTEXT],
+            "id": credential_id,
+            "type": ["VerifiableCredential", CREDENTIAL_TYPE],
+            "issuer": {
+                "id": issuer_id,
+                "name": institution.name,
+                "url": institution.website,
+                "image": institution.logo_url,
+                "profile": institution_profile_url(public_base_url, institution)
+            },
+            "issuanceDate": minted_at.to_rfc3339(),
+            "credentialSubject": {
+                "id": subject_id,
+                "name": cert.recipient_name,
+                "certificate": {
+                    "type": cert.certificate_type,
+                    "fields": cert.fields,
+                    "documentHash": cert.document_hash,
+                    "metadataUri": cert.metadata_uri,
+                    "tokenId": cert.token_id,
+                    "txHash": cert.tx_hash,
+                    "chainId": pool.chain_id,
+                    "contractAddress": pool.contract_address,
+                    "pool": pool.name
+                }
+            }
+        }
+    })
+}
+
+/// Open Badges 3.0 achievement id for a certificate type awarded in a pool.
+pub fn achievement_id(public_base_url: &str, pool: &Pool, certificate_type: &str) -> String {
+    let slug: String = certificate_type
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!(
+        "{}/pools/{}/achievements/{}",
+        public_base_url,
+        pool.code,
+        slug.trim_matches('-')
+    )
+}
+
+/// Open Badges 3.0 `OpenBadgeCredential` for a minted certificate. The pool
+/// is the program and the certificate type the achievement earned in it.
+/// Issued like `certificate_credential`, by the DID of the signing key.
+pub fn open_badge_credential(
+    public_base_url: &str,
+    issuer: Address,
+    cert: &Certificate,
+    pool: &Pool,
+    institution: &Institution,
+    issued_at: DateTime<Utc>,
+) -> serde_json::Value {
+    let issuer_id = signer_did(issuer);
+    let profile_url = institution_profile_url(public_base_url, institution);
+    let minted_at = cert.minted_at.unwrap_or(issued_at);
+
+    let mut credential = serde_json::json!({
+        "@context": [CREDENTIALS_V2_CONTEXT, OPEN_BADGES_CONTEXT],
+        "id": format!("{}/certificates/{}/openbadge", public_base_url, cert.id),
+        "type": ["VerifiableCredential", "OpenBadgeCredential"],
+        "name": format!("{} - {}", cert.certificate_type, pool.name),
+        "issuer": {
+            "id": issuer_id,
+            "type": ["Profile"],
+            "name": institution.name,
+            "url": institution.website,
+            "image": institution.logo_url.as_ref().map(|url| serde_json::json!({
+                "id": url,
+                "type": "Image"
+            }))
+        },
+        "validFrom": minted_at.to_rfc3339(),
+        "credentialSubject": {
+            "id": format!(
+                "did:pkh:eip155:{}:{}",
+                pool.chain_id.unwrap_or(1),
+                cert.recipient_wallet.to_lowercase()
+            ),
+            "type": ["AchievementSubject"],
+            "name": cert.recipient_name,
+            "achievement": {
+                "id": achievement_id(public_base_url, pool, &cert.certificate_type),
+                "type": ["Achievement"],
+                "name": cert.certificate_type,
+                "description": pool.description.as_deref().unwrap_or(&pool.name),
+                "fieldOfStudy": cert.fields.as_ref().and_then(|fields| fields.get("major")),
+                "criteria": {
+                    "narrative": format!("Awarded by {} in {}", institution.name, pool.name)
+                },
+                "creator": { "id": profile_url, "type": ["Profile"], "name": institution.name }
+            }
+        }
+    });
+
+    // Evidence needs an id, so it is only listed when the document has a URI.
+    if let Some(uri) = &cert.metadata_uri {
+        credential["evidence"] = serde_json::json!([{
+            "id": uri,
+            "type": ["Evidence"],
+            "name": "Certificate document",
+            "description": format!("Document hash {}", cert.document_hash)
+        }]);
+    }
+    credential
+}
+
+/// VC-JWT claims for a credential: the credential itself plus the registered
+/// claims mirrored from it.
+pub fn credential_jwt_claims(credential: &serde_json::Value) -> serde_json::Value {
+    let mut claims = credential.clone();
+    let nbf = credential["validFrom"]
+        .as_str()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.timestamp());
+    claims["iss"] = credential["issuer"]["id"].clone();
+    claims["sub"] = credential["credentialSubject"]["id"].clone();
+    claims["jti"] = credential["id"].clone();
+    claims["nbf"] = nbf.into();
+    claims
+}
+
+/// Certificate id from a credential id issued by this backend
+/// (`{base}/certificates/{id}/vc` or `.../openbadge`).
+pub fn credential_certificate_id(public_base_url: &str, credential_id: &str) -> Option<i32> {
+    credential_id
+        .strip_prefix(public_base_url)?
+        .strip_prefix("/certificates/")?
+        .split('/')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Compact ES256K JWS over `claims`.
+pub fn sign_jwt(signer: &ReceiptSigner, claims: &serde_json::Value) -> String {
+    let header = serde_json::json!({
+        "alg": "ES256K",
+        "typ": "JWT",
+        "kid": format!("{}#controller", signer_did(signer.address()))
+    });
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header.to_string()),
+        URL_SAFE_NO_PAD.encode(claims.to_string())
+    );
+    let signature = signer.sign_es256k(signing_input.as_bytes());
+    format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// Checks an ES256K JWT against `key` and returns its claims.
+pub fn verify_jwt(jwt: &str, key: &VerifyingKey) -> Result<serde_json::Value, CredentialError> {
+    let mut parts = jwt.trim().split('.');
+    let (Some(header), Some(claims), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(CredentialError::Malformed);
+    };
+
+    let decode_json = |part: &str| -> Result<serde_json::Value, CredentialError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(part)
+            .map_err(|_| CredentialError::Malformed)?;
+        serde_json::from_slice(&bytes).map_err(|_| CredentialError::Malformed)
+    };
+
+    let alg = decode_json(header)?["alg"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    if alg != "ES256K" {
+        return Err(CredentialError::UnsupportedAlgorithm(alg));
+    }
+
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        .ok_or(CredentialError::Malformed)?;
+    let signing_input = &jwt.trim()[..header.len() + 1 + claims.len()];
+    key.verify(signing_input.as_bytes(), &signature)
+        .map_err(|_| CredentialError::BadSignature)?;
+
+    decode_json(claims)
+}
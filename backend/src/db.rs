@@ -1,5 +1,64 @@
 use sqlx::PgPool;
 
+/// Version of the schema created by `init_db`. Bump it whenever `init_db`
+/// changes, so `/health/ready` can tell when the database lags behind the code.
+pub const SCHEMA_VERSION: i32 = 12;
+
+/// Version recorded by the last successful `init_db`, if any.
+pub async fn schema_version(pool: &PgPool) -> Result<Option<i32>, sqlx::Error> {
+    let row: Option<(i32,)> = sqlx::query_as("SELECT version FROM schema_version WHERE id = true")
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|(version,)| version))
+}
+
+/// Assigns pools created before multi-chain support to the default chain.
+pub async fn backfill_pool_chains(
+    pool: &PgPool,
+    chain_id: u64,
+    contract_address: Option<&str>,
+) -> Result<u64, sqlx::Error> {
+    let result =
+        sqlx::query("UPDATE pools SET chain_id = $1, contract_address = $2 WHERE chain_id IS NULL")
+            .bind(chain_id as i64)
+            .bind(contract_address)
+            .execute(pool)
+            .await?;
+    Ok(result.rows_affected())
+}
+
+/// Fails with the offending certificate ids when rows created before
+/// `certificates_document_hash_key` share a document hash, instead of the
+/// bare index error. Which duplicate to keep is for an operator to decide.
+async fn ensure_unique_document_hashes(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let duplicates: Vec<(String, Vec<i32>)> = sqlx::query_as(
+        r#"
+        SELECT document_hash, ARRAY_AGG(id ORDER BY id) FROM certificates
+        WHERE NOT EXISTS (
+            SELECT 1 FROM pg_indexes WHERE indexname = 'certificates_document_hash_key'
+        )
+        GROUP BY document_hash
+        HAVING COUNT(*) > 1
+        ORDER BY document_hash
+    "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    let rows: Vec<String> = duplicates
+        .iter()
+        .map(|(hash, ids)| format!("{} (certificate ids {:?})", hash, ids))
+        .collect();
+    Err(sqlx::Error::Protocol(format!(
+        "certificates share a document_hash, remove or fix all but one of each before starting: {}",
+        rows.join(", ")
+    )))
+}
+
 pub async fn init_db(pool: &PgPool) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"
@@ -17,6 +76,94 @@ pub async fn init_db(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query(
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT false",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS failed_login_attempts INTEGER NOT NULL DEFAULT 0")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret VARCHAR(64)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT false",
+    )
+    .execute(pool)
+    .await?;
+
+    // Time step of the last accepted TOTP code, so codes cannot be replayed.
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_last_step BIGINT")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS recovery_codes (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            code_hash VARCHAR(64) NOT NULL,
+            used_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS settings (
+            key VARCHAR(100) PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TIMESTAMPTZ DEFAULT NOW()
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS user_tokens (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            purpose VARCHAR(30) NOT NULL,
+            token_hash VARCHAR(64) UNIQUE NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL,
+            used_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS institutions (
+            id SERIAL PRIMARY KEY,
+            institution_id VARCHAR(100) UNIQUE NOT NULL,
+            name VARCHAR(255) NOT NULL,
+            description TEXT,
+            logo_url TEXT,
+            website TEXT,
+            verified_domains TEXT[] NOT NULL DEFAULT '{}',
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
     sqlx::query(
         r#"
         CREATE TABLE IF NOT EXISTS validator_requests (
@@ -53,6 +200,44 @@ pub async fn init_db(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query("ALTER TABLE pools ADD COLUMN IF NOT EXISTS chain_id BIGINT")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE pools ADD COLUMN IF NOT EXISTS contract_address VARCHAR(42)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE pools ADD COLUMN IF NOT EXISTS onchain_pool_id BIGINT")
+        .execute(pool)
+        .await?;
+
+    // The institution a pool issues for is fixed when it is created, whatever
+    // later happens to its validator's request.
+    sqlx::query("ALTER TABLE pools ADD COLUMN IF NOT EXISTS institution_id INTEGER REFERENCES institutions(id)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE pools p SET institution_id = i.id
+        FROM validator_requests vr
+        JOIN institutions i ON i.institution_id = vr.institution_id
+        WHERE p.institution_id IS NULL AND vr.user_id = p.validator_id AND vr.status = 'approved'
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE UNIQUE INDEX IF NOT EXISTS pools_onchain_pool_key
+        ON pools (chain_id, contract_address, onchain_pool_id)
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
     sqlx::query(
         r#"
         CREATE TABLE IF NOT EXISTS certificates (
@@ -77,27 +262,208 @@ pub async fn init_db(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
-    let admin_exists: (i64,) =
-        sqlx::query_as("SELECT COUNT(*) FROM users WHERE email = 'admin@admin.com'")
-            .fetch_one(pool)
-            .await?;
+    sqlx::query("ALTER TABLE certificates ADD COLUMN IF NOT EXISTS onchain_pool_id BIGINT")
+        .execute(pool)
+        .await?;
 
-    if admin_exists.0 == 0 {
-        let password_hash =
-            bcrypt::hash("admin123", bcrypt::DEFAULT_COST).expect("Failed to hash password");
+    sqlx::query("ALTER TABLE certificates ADD COLUMN IF NOT EXISTS onchain_request_id BIGINT")
+        .execute(pool)
+        .await?;
 
-        sqlx::query(
-            r#"
-            INSERT INTO users (email, password_hash, username, role)
-            VALUES ('admin@admin.com', $1, 'admin', 'admin')
-        "#,
+    sqlx::query("ALTER TABLE certificates ADD COLUMN IF NOT EXISTS request_tx_hash VARCHAR(66)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE pools ADD COLUMN IF NOT EXISTS certificate_template JSONB")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE certificates ADD COLUMN IF NOT EXISTS fields JSONB")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE certificates ADD COLUMN IF NOT EXISTS fields_root VARCHAR(66)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE certificates ADD COLUMN IF NOT EXISTS field_salts JSONB")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE pools ADD COLUMN IF NOT EXISTS privacy JSONB")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS share_links (
+            id SERIAL PRIMARY KEY,
+            certificate_id INTEGER NOT NULL REFERENCES certificates(id) ON DELETE CASCADE,
+            token_hash VARCHAR(64) UNIQUE NOT NULL,
+            expires_at TIMESTAMPTZ,
+            revoked_at TIMESTAMPTZ,
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS erasure_requests (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER REFERENCES users(id),
+            wallet VARCHAR(42),
+            reason TEXT,
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            reviewed_by INTEGER REFERENCES users(id),
+            reviewed_at TIMESTAMPTZ,
+            rejection_reason TEXT,
+            result JSONB,
+            created_at TIMESTAMPTZ DEFAULT NOW()
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // One pending erasure per subject, even for concurrent requests.
+    sqlx::query(
+        r#"
+        CREATE UNIQUE INDEX IF NOT EXISTS erasure_requests_pending_key
+        ON erasure_requests ((COALESCE(user_id::text, wallet))) WHERE status = 'pending'
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    ensure_unique_document_hashes(pool).await?;
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS certificates_document_hash_key ON certificates (document_hash)",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_events (
+            id BIGSERIAL PRIMARY KEY,
+            actor VARCHAR(66) NOT NULL,
+            actor_type VARCHAR(20) NOT NULL,
+            action VARCHAR(64) NOT NULL,
+            target_type VARCHAR(32) NOT NULL,
+            target_id VARCHAR(66) NOT NULL,
+            before JSONB,
+            after JSONB,
+            ip VARCHAR(64),
+            user_agent TEXT,
+            prev_hash VARCHAR(66),
+            entry_hash VARCHAR(66),
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
         )
-        .bind(&password_hash)
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS audit_events_target_idx ON audit_events (target_type, target_id)",
+    )
+    .execute(pool)
+    .await?;
+
+    // Audit events are never changed once written.
+    sqlx::query(
+        r#"
+        CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS trigger AS $$
+        BEGIN
+            RAISE EXCEPTION 'audit_events is append-only';
+        END;
+        $$ LANGUAGE plpgsql
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("DROP TRIGGER IF EXISTS audit_events_append_only ON audit_events")
         .execute(pool)
         .await?;
 
-        println!("✅ Admin user seeded: admin@admin.com / admin123");
+    sqlx::query(
+        r#"
+        CREATE TRIGGER audit_events_append_only
+        BEFORE UPDATE OR DELETE ON audit_events
+        FOR EACH ROW EXECUTE FUNCTION audit_events_append_only()
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO institutions (institution_id, name)
+        SELECT DISTINCT ON (institution_id) institution_id, institution_name
+        FROM validator_requests
+        WHERE status = 'approved'
+        ORDER BY institution_id, created_at ASC
+        ON CONFLICT (institution_id) DO NOTHING
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+            version INTEGER NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )
+    "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO schema_version (id, version) VALUES (true, $1)
+        ON CONFLICT (id) DO UPDATE
+        SET version = GREATEST(schema_version.version, EXCLUDED.version), applied_at = NOW()
+    "#,
+    )
+    .bind(SCHEMA_VERSION)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Creates the first admin account from the configured bootstrap credentials,
+/// unless an admin already exists.
+pub async fn seed_admin(pool: &PgPool, email: &str, password: &str) -> Result<(), sqlx::Error> {
+    let admin_exists: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE role = 'admin'")
+        .fetch_one(pool)
+        .await?;
+    if admin_exists.0 > 0 {
+        return Ok(());
     }
 
+    let password_hash =
+        bcrypt::hash(password, bcrypt::DEFAULT_COST).expect("Failed to hash password");
+
+    sqlx::query(
+        r#"
+        INSERT INTO users (email, password_hash, username, role, email_verified)
+        VALUES ($1, $2, 'admin', 'admin', true)
+        ON CONFLICT (email) DO NOTHING
+    "#,
+    )
+    .bind(email.to_lowercase())
+    .bind(&password_hash)
+    .execute(pool)
+    .await?;
+
+    tracing::info!(%email, "admin user seeded");
     Ok(())
 }
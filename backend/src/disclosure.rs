@@ -0,0 +1,135 @@
+//! Merkle commitments over certificate fields for selective disclosure.
+//!
+//! Every field becomes a leaf `keccak256(0x00 || salt || keccak256(name) ||
+//! keccak256(value))`, where `value` is the field's JSON encoding and `salt`
+//! is 32 random bytes so undisclosed low-entropy values (a GPA) cannot be
+//! guessed from the root. Leaves are ordered by field name and paired as
+//! `keccak256(0x01 || min(a, b) || max(a, b))`, so a proof is just the list of
+//! sibling hashes; an odd node is carried up unchanged.
+
+use ethers_core::types::H256;
+use ethers_core::utils::keccak256;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A disclosed field with what is needed to check it against the root.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisclosedField {
+    pub name: String,
+    pub value: Value,
+    pub salt: H256,
+    pub proof: Vec<H256>,
+}
+
+pub fn random_salts(fields: &Map<String, Value>) -> Map<String, Value> {
+    let mut rng = rand::thread_rng();
+    fields
+        .keys()
+        .map(|name| {
+            let mut salt = [0u8; 32];
+            rng.fill_bytes(&mut salt);
+            (
+                name.clone(),
+                Value::String(format!("{:#x}", H256::from(salt))),
+            )
+        })
+        .collect()
+}
+
+pub fn leaf_hash(name: &str, value: &Value, salt: H256) -> H256 {
+    let mut data = vec![0x00];
+    data.extend_from_slice(salt.as_bytes());
+    data.extend_from_slice(&keccak256(name));
+    data.extend_from_slice(&keccak256(value.to_string()));
+    H256::from(keccak256(data))
+}
+
+fn node_hash(a: H256, b: H256) -> H256 {
+    let (low, high) = if a <= b { (a, b) } else { (b, a) };
+    let mut data = vec![0x01];
+    data.extend_from_slice(low.as_bytes());
+    data.extend_from_slice(high.as_bytes());
+    H256::from(keccak256(data))
+}
+
+/// Leaves in name order; `None` when a field has no salt.
+fn leaves(fields: &Map<String, Value>, salts: &Map<String, Value>) -> Option<Vec<(String, H256)>> {
+    let mut names: Vec<&String> = fields.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let salt: H256 = salts.get(name)?.as_str()?.parse().ok()?;
+            Some((name.clone(), leaf_hash(name, &fields[name], salt)))
+        })
+        .collect()
+}
+
+fn next_level(level: &[H256]) -> Vec<H256> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [a, b] => node_hash(*a, *b),
+            [a] => *a,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Root committing to every field, `None` without fields or salts.
+pub fn fields_root(fields: &Map<String, Value>, salts: &Map<String, Value>) -> Option<H256> {
+    let mut level: Vec<H256> = leaves(fields, salts)?.into_iter().map(|(_, h)| h).collect();
+    if level.is_empty() {
+        return None;
+    }
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    Some(level[0])
+}
+
+/// Root over `fields` when `salts` holds exactly one valid salt per field.
+pub fn commitment(fields: &Map<String, Value>, salts: &Map<String, Value>) -> Option<H256> {
+    if salts.len() != fields.len() {
+        return None;
+    }
+    fields_root(fields, salts)
+}
+
+/// Disclosure of field `name` with its inclusion proof.
+pub fn disclose(
+    fields: &Map<String, Value>,
+    salts: &Map<String, Value>,
+    name: &str,
+) -> Option<DisclosedField> {
+    let leaves = leaves(fields, salts)?;
+    let mut index = leaves.iter().position(|(leaf_name, _)| leaf_name == name)?;
+    let mut level: Vec<H256> = leaves.into_iter().map(|(_, h)| h).collect();
+
+    let mut proof = Vec::new();
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+
+    Some(DisclosedField {
+        name: name.into(),
+        value: fields[name].clone(),
+        salt: salts.get(name)?.as_str()?.parse().ok()?,
+        proof,
+    })
+}
+
+/// Checks a disclosed field against a committed root. Needs nothing but the
+/// root, so verifiers can run it offline.
+pub fn verify_disclosure(root: H256, field: &DisclosedField) -> bool {
+    let computed = field.proof.iter().fold(
+        leaf_hash(&field.name, &field.value, field.salt),
+        |hash, sibling| node_hash(hash, *sibling),
+    );
+    computed == root
+}
@@ -1,6 +1,17 @@
+use actix_web::http::StatusCode;
 use actix_web::HttpResponse;
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::chain::ChainError;
+use crate::middleware::request_id;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
 #[derive(Debug, Error)]
 pub enum ApiError {
     #[error("Unauthorized")]
@@ -15,33 +26,153 @@ pub enum ApiError {
     #[error("Not found")]
     NotFound,
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Request body exceeds {0} bytes")]
+    PayloadTooLarge(usize),
+
+    #[error("Too many requests, retry in {0} seconds")]
+    TooManyRequests(u64),
+
+    #[error("Validation failed")]
+    Validation(Vec<FieldError>),
+
+    #[error("Upstream service failed: {0}")]
+    Upstream(String),
+
     #[error("Internal server error")]
     Internal,
 }
 
+impl ApiError {
+    /// Shorthand for a validation error on a single field.
+    pub fn invalid(field: &str, message: impl Into<String>) -> Self {
+        ApiError::Validation(vec![FieldError {
+            field: field.into(),
+            message: message.into(),
+        }])
+    }
+
+    /// Stable machine-readable code, sent as `error` in every error body.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden => "forbidden",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::NotFound => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::TooManyRequests(_) => "too_many_requests",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Upstream(_) => "upstream_error",
+            ApiError::Internal => "internal_error",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ApiError::BadRequest(msg) | ApiError::Conflict(msg) => msg.clone(),
+            _ => self.to_string(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            ApiError::TooManyRequests(retry_after) => {
+                Some(serde_json::json!({ "retry_after": retry_after }))
+            }
+            ApiError::Validation(fields) => Some(serde_json::json!({ "fields": fields })),
+            _ => None,
+        }
+    }
+}
+
+/// Conflict message for a unique constraint, named after the Postgres
+/// default `<table>_<column>_key`.
+fn conflict_message(constraint: Option<&str>) -> String {
+    match constraint {
+        Some("users_email_key") => "Email already registered".into(),
+        Some("certificates_document_hash_key") => "Certificate already submitted".into(),
+        Some("institutions_institution_id_key") => "Institution already exists".into(),
+        Some("pools_code_key") => "Pool code already in use".into(),
+        Some("pools_onchain_pool_key") => "On-chain pool already registered".into(),
+        Some("erasure_requests_pending_key") => "An erasure request is already pending".into(),
+        Some(name) => format!("Duplicate value violates {}", name),
+        None => "Duplicate value".into(),
+    }
+}
+
+impl From<ChainError> for ApiError {
+    fn from(err: ChainError) -> Self {
+        tracing::warn!(error = %err, "chain request failed");
+        ApiError::Upstream(err.to_string())
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        // `RowNotFound` only comes from `fetch_one` on rows that must exist,
+        // so it is an inconsistency, not a 404; lookups of client-supplied
+        // ids use `fetch_optional` and return `NotFound` themselves.
+        match &err {
+            sqlx::Error::Database(db_err) => match db_err.code().as_deref() {
+                // unique_violation
+                Some("23505") => ApiError::Conflict(conflict_message(db_err.constraint())),
+                // foreign_key_violation
+                Some("23503") => ApiError::BadRequest("Referenced record does not exist".into()),
+                // not_null_violation, string_data_right_truncation, check_violation
+                Some("23502") | Some("22001") | Some("23514") => {
+                    let field = db_err
+                        .try_downcast_ref::<sqlx::postgres::PgDatabaseError>()
+                        .and_then(|e| e.column())
+                        .or(db_err.constraint())
+                        .unwrap_or("unknown");
+                    ApiError::invalid(field, db_err.message())
+                }
+                _ => {
+                    tracing::error!(error = %err, "database error");
+                    ApiError::Internal
+                }
+            },
+            _ => {
+                tracing::error!(error = %err, "database error");
+                ApiError::Internal
+            }
+        }
+    }
+}
+
 impl actix_web::ResponseError for ApiError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self {
-            ApiError::Unauthorized => HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "unauthorized",
-                "message": self.to_string()
-            })),
-            ApiError::Forbidden => HttpResponse::Forbidden().json(serde_json::json!({
-                "error": "forbidden",
-                "message": self.to_string()
-            })),
-            ApiError::BadRequest(msg) => HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "bad_request",
-                "message": msg
-            })),
-            ApiError::NotFound => HttpResponse::NotFound().json(serde_json::json!({
-                "error": "not_found",
-                "message": self.to_string()
-            })),
-            ApiError::Internal => HttpResponse::InternalServerError().json(serde_json::json!({
-                "error": "internal_error",
-                "message": self.to_string()
-            })),
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden => StatusCode::FORBIDDEN,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = serde_json::json!({
+            "error": self.code(),
+            "message": self.message(),
+            "request_id": request_id::current()
+        });
+        if let Some(details) = self.details() {
+            body["details"] = details;
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let ApiError::TooManyRequests(retry_after) = self {
+            response.insert_header(("Retry-After", retry_after.to_string()));
         }
+        response.json(body)
     }
 }
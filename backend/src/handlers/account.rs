@@ -0,0 +1,306 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+
+use crate::errors::ApiError;
+use crate::mailer::Email;
+use crate::middleware::rate_limit::limit_key;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::state::AppState;
+
+const VERIFY_EMAIL_PURPOSE: &str = "verify_email";
+const VERIFY_EMAIL_TTL_HOURS: i64 = 24;
+const PASSWORD_RESET_PURPOSE: &str = "password_reset";
+const PASSWORD_RESET_TTL_HOURS: i64 = 1;
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// Random token handed out in email links. Only its hash is stored.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+pub fn validate_password(field: &str, password: &str) -> Result<(), ApiError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(ApiError::invalid(
+            field,
+            format!("must be at least {} characters", MIN_PASSWORD_LEN),
+        ));
+    }
+    Ok(())
+}
+
+/// Issues a new single-use token, invalidating any unused token the user
+/// still has for the same purpose.
+async fn create_user_token(
+    db: &PgPool,
+    user_id: i32,
+    purpose: &str,
+    ttl: Duration,
+) -> Result<String, ApiError> {
+    sqlx::query(
+        "UPDATE user_tokens SET used_at = NOW() WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .execute(db)
+    .await?;
+
+    let token = generate_token();
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_tokens (user_id, purpose, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+    "#,
+    )
+    .bind(user_id)
+    .bind(purpose)
+    .bind(hash_token(&token))
+    .bind(Utc::now() + ttl)
+    .execute(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Marks the token as used and returns its user, if it is valid for `purpose`.
+async fn consume_user_token(db: &PgPool, token: &str, purpose: &str) -> Result<i32, ApiError> {
+    let row: Option<(i32,)> = sqlx::query_as(
+        r#"
+        UPDATE user_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND purpose = $2 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id
+    "#,
+    )
+    .bind(hash_token(token))
+    .bind(purpose)
+    .fetch_optional(db)
+    .await?;
+
+    row.map(|(user_id,)| user_id)
+        .ok_or_else(|| ApiError::BadRequest("Invalid or expired token".into()))
+}
+
+pub fn verification_email(user: &User, link: &str) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Verify your Etched email address".into(),
+        body: format!(
+            "Hi {},\n\nConfirm your email address by opening the link below. \
+             It expires in {} hours.\n\n{}\n",
+            user.username, VERIFY_EMAIL_TTL_HOURS, link
+        ),
+    }
+}
+
+pub fn password_reset_email(user: &User, link: &str) -> Email {
+    Email {
+        to: user.email.clone(),
+        subject: "Reset your Etched password".into(),
+        body: format!(
+            "Hi {},\n\nSomeone asked to reset the password of your Etched account. \
+             If it was you, open the link below within {} hour(s). \
+             Otherwise you can ignore this email.\n\n{}\n",
+            user.username, PASSWORD_RESET_TTL_HOURS, link
+        ),
+    }
+}
+
+async fn deliver(state: &AppState, email: Email) -> Result<(), ApiError> {
+    // SMTP errors name hosts and accounts; they only go to the log.
+    state.mailer.send(email).await.map_err(|e| {
+        tracing::error!(error = %e, "mail delivery failed");
+        ApiError::Upstream("mail delivery failed".into())
+    })
+}
+
+pub async fn send_verification_email(state: &AppState, user: &User) -> Result<(), ApiError> {
+    let token = create_user_token(
+        &state.db,
+        user.id,
+        VERIFY_EMAIL_PURPOSE,
+        Duration::hours(VERIFY_EMAIL_TTL_HOURS),
+    )
+    .await?;
+    let link = format!(
+        "{}/auth/verify-email?token={}",
+        state.config.public_base_url, token
+    );
+
+    deliver(state, verification_email(user, &link)).await
+}
+
+#[get("/auth/verify-email")]
+pub async fn verify_email(
+    state: web::Data<AppState>,
+    query: web::Query<VerifyEmailQuery>,
+) -> Result<impl Responder, ApiError> {
+    let user_id = consume_user_token(&state.db, &query.token, VERIFY_EMAIL_PURPOSE).await?;
+
+    let email: (String,) =
+        sqlx::query_as("UPDATE users SET email_verified = true WHERE id = $1 RETURNING email")
+            .bind(user_id)
+            .fetch_one(&state.db)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Email verified",
+        "email": email.0
+    })))
+}
+
+#[post("/auth/verify-email/resend")]
+pub async fn resend_verification_email(
+    state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "email" {
+        return Err(ApiError::BadRequest(
+            "Only email users can verify an email address".into(),
+        ));
+    }
+
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    let db_user: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if db_user.email_verified {
+        return Err(ApiError::BadRequest("Email already verified".into()));
+    }
+
+    send_verification_email(&state, &db_user).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Verification email sent"
+    })))
+}
+
+#[post("/auth/password/forgot")]
+pub async fn forgot_password(
+    state: web::Data<AppState>,
+    payload: web::Json<ForgotPasswordRequest>,
+) -> Result<impl Responder, ApiError> {
+    let email = payload.email.trim().to_lowercase();
+    limit_key(&state, &format!("forgot:{}", email))?;
+
+    let user: Option<User> = sqlx::query_as("SELECT * FROM users WHERE email = $1")
+        .bind(&email)
+        .fetch_optional(&state.db)
+        .await?;
+
+    // Same answer whether or not the account exists, so this endpoint cannot be
+    // used to find out which emails are registered.
+    if let Some(user) = user {
+        let token = create_user_token(
+            &state.db,
+            user.id,
+            PASSWORD_RESET_PURPOSE,
+            Duration::hours(PASSWORD_RESET_TTL_HOURS),
+        )
+        .await?;
+        let link = format!(
+            "{}/reset-password?token={}",
+            state.config.frontend_url, token
+        );
+
+        // A delivery failure must not change the answer either; `deliver`
+        // has logged it.
+        let _ = deliver(&state, password_reset_email(&user, &link)).await;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "If an account exists for this email, a reset link has been sent"
+    })))
+}
+
+#[post("/auth/password/reset")]
+pub async fn reset_password(
+    state: web::Data<AppState>,
+    payload: web::Json<ResetPasswordRequest>,
+) -> Result<impl Responder, ApiError> {
+    validate_password("new_password", &payload.new_password)?;
+
+    let user_id = consume_user_token(&state.db, &payload.token, PASSWORD_RESET_PURPOSE).await?;
+
+    let password_hash = bcrypt::hash(&payload.new_password, bcrypt::DEFAULT_COST)
+        .map_err(|_| ApiError::Internal)?;
+
+    // The reset link proves control of the mailbox, so it also verifies the email
+    // and lifts any lockout.
+    sqlx::query(
+        r#"
+        UPDATE users
+        SET password_hash = $1, email_verified = true, failed_login_attempts = 0, locked_until = NULL
+        WHERE id = $2
+    "#,
+    )
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Password has been reset"
+    })))
+}
+
+#[post("/auth/password/change")]
+pub async fn change_password(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    payload: web::Json<ChangePasswordRequest>,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "email" {
+        return Err(ApiError::BadRequest(
+            "Only email users have a password".into(),
+        ));
+    }
+
+    validate_password("new_password", &payload.new_password)?;
+
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    let db_user: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let valid = bcrypt::verify(&payload.current_password, &db_user.password_hash)
+        .map_err(|_| ApiError::Internal)?;
+    if !valid {
+        return Err(ApiError::BadRequest("Current password is incorrect".into()));
+    }
+
+    let password_hash = bcrypt::hash(&payload.new_password, bcrypt::DEFAULT_COST)
+        .map_err(|_| ApiError::Internal)?;
+
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(&state.db)
+        .await?;
+
+    sqlx::query(
+        "UPDATE user_tokens SET used_at = NOW() WHERE user_id = $1 AND purpose = $2 AND used_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(PASSWORD_RESET_PURPOSE)
+    .execute(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Password changed"
+    })))
+}
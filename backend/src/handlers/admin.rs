@@ -1,11 +1,22 @@
 use actix_web::{get, post, web, HttpResponse, Responder};
 use chrono::Utc;
+use sqlx::types::Json;
 
+use crate::audit::{self, AuditEntry, ClientInfo};
+use crate::config::format_eth;
 use crate::errors::ApiError;
+use crate::handlers::institutions::email_domain_matches;
+use crate::handlers::two_factor::ensure_two_factor;
 use crate::middleware::AuthUser;
 use crate::models::*;
 use crate::state::AppState;
 
+type PendingValidatorRow = (
+    Json<ValidatorRequest>,
+    Json<User>,
+    Option<Json<Institution>>,
+);
+
 #[get("/admin/validator-requests")]
 pub async fn list_validator_requests(
     state: web::Data<AppState>,
@@ -15,24 +26,37 @@ pub async fn list_validator_requests(
         return Err(ApiError::Forbidden);
     }
 
-    let requests: Vec<ValidatorRequest> = sqlx::query_as(
-        "SELECT * FROM validator_requests WHERE status = 'pending' ORDER BY created_at ASC",
+    // Each row with its applicant and institution in one round trip.
+    let rows: Vec<PendingValidatorRow> = sqlx::query_as(
+        r#"
+        SELECT to_jsonb(vr), to_jsonb(u), to_jsonb(i)
+        FROM validator_requests vr
+        JOIN users u ON u.id = vr.user_id
+        LEFT JOIN institutions i ON i.institution_id = vr.institution_id
+        WHERE vr.status = 'pending'
+        ORDER BY vr.created_at ASC
+    "#,
     )
     .fetch_all(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    .await?;
 
     let mut results = Vec::new();
-    for req in requests {
-        let user: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
-            .bind(req.user_id)
-            .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+    for (Json(req), Json(user), institution) in rows {
+        let institution = institution.map(|Json(institution)| institution);
+        let domain_match = institution
+            .as_ref()
+            .map(|i| email_domain_matches(&user.email, &i.verified_domains))
+            .unwrap_or(false);
 
         results.push(serde_json::json!({
+            "verification": {
+                "email_verified": user.email_verified,
+                "email_domain_match": domain_match,
+                "institution_domain_verified": user.email_verified && domain_match
+            },
             "request": req,
-            "user": UserPublic::from(user)
+            "user": UserPublic::from(user),
+            "institution": institution
         }));
     }
 
@@ -43,6 +67,7 @@ pub async fn list_validator_requests(
 pub async fn decide_validator_request(
     state: web::Data<AppState>,
     user: AuthUser,
+    client: ClientInfo,
     path: web::Path<i32>,
     payload: web::Json<ValidatorDecisionRequest>,
 ) -> Result<impl Responder, ApiError> {
@@ -52,13 +77,13 @@ pub async fn decide_validator_request(
 
     let request_id = path.into_inner();
     let admin_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    ensure_two_factor(&state.db, admin_id).await?;
 
-    let _request: ValidatorRequest =
+    let request: ValidatorRequest =
         sqlx::query_as("SELECT * FROM validator_requests WHERE id = $1")
             .bind(request_id)
             .fetch_optional(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?
+            .await?
             .ok_or(ApiError::NotFound)?;
 
     let status = if payload.approve {
@@ -67,11 +92,14 @@ pub async fn decide_validator_request(
         "rejected"
     };
 
-    sqlx::query(
+    let mut tx = state.db.begin().await?;
+
+    let decided: Option<(i32,)> = sqlx::query_as(
         r#"
         UPDATE validator_requests 
         SET status = $1, reviewed_by = $2, reviewed_at = $3, rejection_reason = $4
-        WHERE id = $5
+        WHERE id = $5 AND status = 'pending'
+        RETURNING id
     "#,
     )
     .bind(status)
@@ -79,9 +107,44 @@ pub async fn decide_validator_request(
     .bind(Utc::now())
     .bind(&payload.rejection_reason)
     .bind(request_id)
-    .execute(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    .fetch_optional(&mut *tx)
+    .await?;
+    if decided.is_none() {
+        return Err(ApiError::Conflict(
+            "Validator request already processed".into(),
+        ));
+    }
+
+    if payload.approve {
+        sqlx::query(
+            r#"
+            INSERT INTO institutions (institution_id, name)
+            VALUES ($1, $2)
+            ON CONFLICT (institution_id) DO NOTHING
+        "#,
+        )
+        .bind(&request.institution_id)
+        .bind(&request.institution_name)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("validator_request.decide", "validator_request", request_id).change(
+            serde_json::json!({ "status": request.status }),
+            serde_json::json!({
+                "status": status,
+                "rejection_reason": payload.rejection_reason
+            }),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": format!("Validator request {}", status),
@@ -106,8 +169,7 @@ pub async fn list_validators(
     "#,
     )
     .fetch_all(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    .await?;
 
     let mut results = Vec::new();
     for u in users {
@@ -116,8 +178,7 @@ pub async fn list_validators(
         )
         .bind(u.id)
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
         results.push(serde_json::json!({
             "user": UserPublic::from(u),
@@ -142,25 +203,21 @@ pub async fn admin_stats(
     let pending_requests: (i64,) =
         sqlx::query_as("SELECT COUNT(*) FROM validator_requests WHERE status = 'pending'")
             .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
     let total_validators: (i64,) =
         sqlx::query_as("SELECT COUNT(*) FROM validator_requests WHERE status = 'approved'")
             .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
     let total_pools: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM pools")
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
     let total_certificates: (i64,) =
         sqlx::query_as("SELECT COUNT(*) FROM certificates WHERE status = 'minted'")
             .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "pending_requests": pending_requests.0,
@@ -168,6 +225,7 @@ pub async fn admin_stats(
         "total_pools": total_pools.0,
         "total_certificates": total_certificates.0,
         "admin_wallet": state.config.admin_wallet,
-        "pool_cost_eth": state.config.pool_cost_eth
+        "pool_cost_eth": format_eth(state.config.pool_cost_wei),
+        "pool_cost_wei": state.config.pool_cost_wei.to_string()
     })))
 }
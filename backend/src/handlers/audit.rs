@@ -0,0 +1,115 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use ethers_core::types::H256;
+
+use crate::audit::check_chain;
+use crate::errors::ApiError;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::state::AppState;
+
+const DEFAULT_AUDIT_LIMIT: i64 = 50;
+const MAX_AUDIT_LIMIT: i64 = 500;
+const CHAIN_CHECK_BATCH: i64 = 1000;
+
+#[get("/admin/audit")]
+pub async fn list_audit_events(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    query: web::Query<AuditQuery>,
+) -> Result<impl Responder, ApiError> {
+    if user.role != "admin" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT);
+    if !(1..=MAX_AUDIT_LIMIT).contains(&limit) {
+        return Err(ApiError::invalid(
+            "limit",
+            format!("must be between 1 and {}", MAX_AUDIT_LIMIT),
+        ));
+    }
+
+    let events: Vec<AuditEvent> = sqlx::query_as(
+        r#"
+        SELECT * FROM audit_events
+        WHERE ($1::text IS NULL OR actor = LOWER($1))
+          AND ($2::text IS NULL OR action = $2)
+          AND ($3::text IS NULL OR target_type = $3)
+          AND ($4::text IS NULL OR target_id = $4)
+          AND ($5::timestamptz IS NULL OR created_at >= $5)
+          AND ($6::timestamptz IS NULL OR created_at < $6)
+          AND ($7::bigint IS NULL OR id < $7)
+        ORDER BY id DESC
+        LIMIT $8
+    "#,
+    )
+    .bind(&query.actor)
+    .bind(&query.action)
+    .bind(&query.target_type)
+    .bind(&query.target_id)
+    .bind(query.since)
+    .bind(query.until)
+    .bind(query.before_id)
+    .bind(limit)
+    .fetch_all(&state.db)
+    .await?;
+
+    let next_before_id = (events.len() as i64 == limit)
+        .then(|| events.last().map(|event| event.id))
+        .flatten();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "events": events,
+        "next_before_id": next_before_id
+    })))
+}
+
+/// Recomputes the hash chain over every chained event.
+#[get("/admin/audit/verify")]
+pub async fn verify_audit_chain(
+    state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<impl Responder, ApiError> {
+    if user.role != "admin" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let mut prev = H256::zero();
+    let mut last_id = 0i64;
+    let mut checked = 0usize;
+    loop {
+        let events: Vec<AuditEvent> = sqlx::query_as(
+            r#"
+            SELECT * FROM audit_events
+            WHERE entry_hash IS NOT NULL AND id > $1
+            ORDER BY id ASC
+            LIMIT $2
+        "#,
+        )
+        .bind(last_id)
+        .bind(CHAIN_CHECK_BATCH)
+        .fetch_all(&state.db)
+        .await?;
+        let Some(last) = events.last() else { break };
+        last_id = last.id;
+
+        match check_chain(prev, &events) {
+            Ok(hash) => prev = hash,
+            Err(broken_id) => {
+                return Ok(HttpResponse::Ok().json(serde_json::json!({
+                    "valid": false,
+                    "checked": checked + events.iter().take_while(|e| e.id != broken_id).count(),
+                    "broken_at": broken_id
+                })))
+            }
+        }
+        checked += events.len();
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "valid": true,
+        "checked": checked,
+        "head": (checked > 0).then(|| format!("{:#x}", prev)),
+        "enabled": state.config.audit_hash_chain
+    })))
+}
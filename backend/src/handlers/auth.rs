@@ -4,8 +4,15 @@ use ethers_core::types::Signature;
 use ethers_core::utils::hash_message;
 use jsonwebtoken::{encode, EncodingKey, Header};
 use std::str::FromStr;
+use std::sync::OnceLock;
 
+use crate::audit::{self, AuditEntry, ClientInfo};
 use crate::errors::ApiError;
+use crate::handlers::account::send_verification_email;
+use crate::handlers::institutions::find_institution;
+use crate::handlers::two_factor::{issue_challenge_token, two_factor_policy};
+use crate::logging::redact;
+use crate::middleware::rate_limit::{limit_key, lockout_duration};
 use crate::middleware::AuthUser;
 use crate::models::*;
 use crate::state::AppState;
@@ -18,21 +25,101 @@ pub async fn login(
     payload: web::Json<LoginRequest>,
 ) -> Result<impl Responder, ApiError> {
     let email = payload.email.to_lowercase();
+    limit_key(&state, &format!("login:{}", email))?;
 
-    let user: User = sqlx::query_as("SELECT * FROM users WHERE email = $1")
+    let user: Option<User> = sqlx::query_as("SELECT * FROM users WHERE email = $1")
         .bind(&email)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
-        .ok_or(ApiError::Unauthorized)?;
+        .await?;
+
+    // Unknown emails and locked accounts fail exactly like a wrong password,
+    // after the same hashing work, so the response reveals neither.
+    let Some(user) = user else {
+        let _ = bcrypt::verify(&payload.password, unknown_user_hash());
+        tracing::info!("login failed: unknown email");
+        return Err(ApiError::Unauthorized);
+    };
 
     let valid =
         bcrypt::verify(&payload.password, &user.password_hash).map_err(|_| ApiError::Internal)?;
 
     if !valid {
+        tracing::info!(user_id = user.id, "login failed: wrong password");
+        record_failed_login(&state, user.id).await?;
+        return Err(ApiError::Unauthorized);
+    }
+
+    if is_locked(&user) {
+        tracing::info!(user_id = user.id, "login refused: account locked");
         return Err(ApiError::Unauthorized);
     }
 
+    if user.totp_enabled {
+        let challenge_token = issue_challenge_token(&state.config.jwt_secret, user.id)?;
+        return Ok(HttpResponse::Ok().json(TwoFactorChallengeResponse {
+            two_factor_required: true,
+            challenge_token,
+        }));
+    }
+
+    email_login_response(&state, user).await
+}
+
+/// Hash checked against for unknown emails, so they cost as much as a known one.
+fn unknown_user_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| {
+        bcrypt::hash(uuid::Uuid::new_v4().to_string(), bcrypt::DEFAULT_COST)
+            .expect("Failed to hash password")
+    })
+}
+
+/// Whether failed login attempts currently lock the account.
+pub fn is_locked(user: &User) -> bool {
+    user.locked_until.is_some_and(|until| until > Utc::now())
+}
+
+/// Counts a failed password or second-factor attempt, locking the account
+/// once too many add up.
+pub async fn record_failed_login(state: &AppState, user_id: i32) -> Result<(), ApiError> {
+    let attempts: (i32,) = sqlx::query_as(
+        r#"
+        UPDATE users SET failed_login_attempts = failed_login_attempts + 1
+        WHERE id = $1
+        RETURNING failed_login_attempts
+    "#,
+    )
+    .bind(user_id)
+    .fetch_one(&state.db)
+    .await?;
+
+    if let Some(lockout) = lockout_duration(
+        attempts.0,
+        state.config.lockout_threshold,
+        state.config.lockout_base_secs,
+        state.config.lockout_max_secs,
+    ) {
+        sqlx::query("UPDATE users SET locked_until = $1 WHERE id = $2")
+            .bind(Utc::now() + lockout)
+            .bind(user_id)
+            .execute(&state.db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Issues the session JWT of an email account once every login step passed.
+pub async fn email_login_response(state: &AppState, user: User) -> Result<HttpResponse, ApiError> {
+    if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+        sqlx::query(
+            "UPDATE users SET failed_login_attempts = 0, locked_until = NULL WHERE id = $1",
+        )
+        .bind(user.id)
+        .execute(&state.db)
+        .await?;
+    }
+
     let exp = (Utc::now().timestamp() + 60 * 60 * 24) as usize;
     let claims = Claims {
         sub: user.id.to_string(),
@@ -48,9 +135,13 @@ pub async fn login(
     )
     .map_err(|_| ApiError::Internal)?;
 
+    let two_factor_setup_required =
+        !user.totp_enabled && two_factor_policy(&state.db).await?.required_for(&user.role);
+
     Ok(HttpResponse::Ok().json(LoginResponse {
         token,
         role: user.role.clone(),
+        two_factor_setup_required,
         user: user.into(),
     }))
 }
@@ -65,13 +156,33 @@ pub async fn register(
     let exists: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE email = $1")
         .bind(&email)
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
     if exists.0 > 0 {
-        return Err(ApiError::BadRequest("Email already registered".into()));
+        return Err(ApiError::Conflict("Email already registered".into()));
+    }
+
+    let institution_id = payload.institution_id.trim();
+    if institution_id.is_empty() {
+        return Err(ApiError::invalid("institution_id", "is required"));
     }
 
+    // An unknown institution is only proposed here; it is created when an admin
+    // approves the request.
+    let institution = find_institution(&state.db, institution_id).await?;
+    let institution_name = match &institution {
+        Some(institution) => institution.name.clone(),
+        None => payload
+            .institution_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| {
+                ApiError::invalid("institution_name", "is required for a new institution")
+            })?
+            .to_string(),
+    };
+
     let password_hash =
         bcrypt::hash(&payload.password, bcrypt::DEFAULT_COST).map_err(|_| ApiError::Internal)?;
 
@@ -86,8 +197,7 @@ pub async fn register(
     .bind(&password_hash)
     .bind(&payload.username)
     .fetch_one(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    .await?;
 
     sqlx::query(
         r#"
@@ -96,16 +206,19 @@ pub async fn register(
     "#,
     )
     .bind(user.id)
-    .bind(&payload.institution_name)
-    .bind(&payload.institution_id)
+    .bind(&institution_name)
+    .bind(institution_id)
     .bind(&payload.document_url)
     .execute(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    .await?;
+
+    let verification_sent = send_verification_email(&state, &user).await.is_ok();
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": "Registration successful. Please wait for admin approval.",
-        "user": UserPublic::from(user)
+        "message": "Registration successful. Please verify your email and wait for admin approval.",
+        "verification_sent": verification_sent,
+        "user": UserPublic::from(user),
+        "institution": institution
     })))
 }
 
@@ -119,12 +232,13 @@ pub async fn get_nonce(
     if !address.starts_with("0x") || address.len() != 42 {
         return Err(ApiError::BadRequest("Invalid address format".into()));
     }
+    limit_key(&state, &format!("nonce:{}", address))?;
 
     let nonce = uuid::Uuid::new_v4().to_string();
     let message = format!("{}: {}", SIGNING_MESSAGE_PREFIX, nonce);
 
     let mut nonces = state.nonces.lock().map_err(|_| ApiError::Internal)?;
-    println!("Storing nonce for address: {}", address);
+    tracing::debug!(%address, nonce = %redact(&nonce), "nonce issued");
     nonces.insert(address, nonce.clone());
 
     Ok(HttpResponse::Ok().json(NonceResponse { nonce, message }))
@@ -136,47 +250,42 @@ pub async fn verify_wallet(
     payload: web::Json<VerifyWalletRequest>,
 ) -> Result<impl Responder, ApiError> {
     let address = payload.address.trim().to_lowercase();
+    limit_key(&state, &format!("wallet:{}", address))?;
 
     let nonce = {
         let mut nonces = state.nonces.lock().map_err(|_| ApiError::Internal)?;
         nonces.remove(&address).ok_or_else(|| {
-            println!("Nonce not found for address: {}", address);
-
-            println!("Available keys: {:?}", nonces.keys());
-            ApiError::BadRequest(format!("Nonce not found for {}", address).into())
+            tracing::warn!(%address, pending_nonces = nonces.len(), "nonce not found");
+            ApiError::BadRequest(format!("Nonce not found for {}", address))
         })?
     };
 
     let message = format!("{}: {}", SIGNING_MESSAGE_PREFIX, nonce);
-    println!("Verifying message: {}", message);
+    tracing::debug!(
+        %address,
+        nonce = %redact(&nonce),
+        signature = %redact(payload.signature.trim()),
+        "verifying wallet signature"
+    );
 
     let signature = Signature::from_str(payload.signature.trim())
-        .map_err(|e| ApiError::BadRequest(format!("Invalid signature format: {}", e).into()))?;
+        .map_err(|e| ApiError::BadRequest(format!("Invalid signature format: {}", e)))?;
 
     let message_hash = hash_message(&message);
     let recovered = signature
         .recover(message_hash)
-        .map_err(|e| ApiError::BadRequest(format!("Signature recovery failed: {}", e).into()))?;
+        .map_err(|e| ApiError::BadRequest(format!("Signature recovery failed: {}", e)))?;
 
     let recovered_addr = format!("0x{:x}", recovered);
-    println!(
-        "Recovered (len {}): {:?}",
-        recovered_addr.len(),
-        recovered_addr
-    );
-    println!("Expected  (len {}): {:?}", address.len(), address);
-
     if recovered_addr != address {
-        return Err(ApiError::BadRequest(
-            format!(
-                "Unauthorized: recovered {} (len {}) != expected {} (len {})",
-                recovered_addr,
-                recovered_addr.len(),
-                address,
-                address.len()
-            )
-            .into(),
-        ));
+        tracing::warn!(expected = %address, recovered = %recovered_addr, "wallet signature mismatch");
+        return Err(ApiError::BadRequest(format!(
+            "Unauthorized: recovered {} (len {}) != expected {} (len {})",
+            recovered_addr,
+            recovered_addr.len(),
+            address,
+            address.len()
+        )));
     }
 
     let exp = (Utc::now().timestamp() + 60 * 60 * 12) as usize;
@@ -218,8 +327,7 @@ pub async fn get_me(
     let db_user: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
         .bind(user_id)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
         .ok_or(ApiError::NotFound)?;
 
     let request: Option<ValidatorRequest> = if db_user.role == "validator" {
@@ -228,8 +336,7 @@ pub async fn get_me(
         )
         .bind(user_id)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
     } else {
         None
     };
@@ -245,6 +352,7 @@ pub async fn get_me(
 pub async fn connect_wallet(
     state: web::Data<AppState>,
     user: AuthUser,
+    client: ClientInfo,
     payload: web::Json<ConnectWalletRequest>,
 ) -> Result<impl Responder, ApiError> {
     if user.auth_type != "email" {
@@ -256,12 +364,34 @@ pub async fn connect_wallet(
     let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
     let wallet = payload.wallet_address.to_lowercase();
 
+    let mut tx = state.db.begin().await?;
+
+    let (previous,): (Option<String>,) =
+        sqlx::query_as("SELECT wallet_address FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+
     sqlx::query("UPDATE users SET wallet_address = $1 WHERE id = $2")
         .bind(&wallet)
         .bind(user_id)
-        .execute(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("user.connect_wallet", "user", user_id).change(
+            serde_json::json!({ "wallet_address": previous }),
+            serde_json::json!({ "wallet_address": wallet }),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Wallet connected successfully",
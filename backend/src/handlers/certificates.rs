@@ -1,10 +1,24 @@
 use actix_web::{get, post, web, HttpResponse, Responder};
 use chrono::Utc;
-
+use ethers_core::types::U256;
+use serde_json::{Map, Value};
+use sqlx::PgConnection;
+use std::sync::Arc;
+
+use crate::audit::{self, AuditEntry, ClientInfo};
+use crate::chain::{ChainClient, OnChainCertificate};
+use crate::disclosure::{commitment, random_salts};
 use crate::errors::ApiError;
+use crate::handlers::disclosure::decode_commitment_token;
+use crate::handlers::institutions::pool_institution;
+use crate::handlers::pools::{onchain_id, parse_tx_hash, pool_chain, pool_client};
+use crate::handlers::two_factor::ensure_two_factor;
+use crate::middleware::rate_limit::limit_key;
 use crate::middleware::AuthUser;
 use crate::models::*;
+use crate::receipt::{recover_signer, verify_receipt, SignedReceipt, VerificationReceipt};
 use crate::state::AppState;
+use crate::templates::validate_fields;
 
 #[post("/pools/{code}/certificates")]
 pub async fn submit_certificate(
@@ -25,50 +39,248 @@ pub async fn submit_certificate(
     let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE code = $1 AND is_active = true")
         .bind(&code)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
         .ok_or_else(|| ApiError::BadRequest("Pool not found or inactive".into()))?;
 
-    let exists: (i64,) =
-        sqlx::query_as("SELECT COUNT(*) FROM certificates WHERE document_hash = $1")
-            .bind(&payload.document_hash)
-            .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+    if let (Some(requested), Some(actual)) = (payload.onchain_pool_id, pool.onchain_pool_id) {
+        if requested != actual {
+            return Err(ApiError::invalid(
+                "onchain_pool_id",
+                format!("pool {} is on-chain pool {}", pool.code, actual),
+            ));
+        }
+    }
+
+    let fields = template_fields(&pool, payload.fields.as_ref())?;
+
+    // The root is fixed before minting, so it can be written into the
+    // document's metadata. Salts handed out earlier, always drawn by the
+    // server, keep an existing root.
+    let commitment = match &fields {
+        Some(fields) if !fields.is_empty() => {
+            let salts = match &payload.commitment_token {
+                Some(token) => {
+                    decode_commitment_token(&state.config.jwt_secret, token, &wallet, &pool.code)?
+                }
+                None => random_salts(fields),
+            };
+            let root = commitment(fields, &salts).ok_or_else(|| {
+                ApiError::invalid("commitment_token", "was issued for different fields")
+            })?;
+            Some((format!("{:#x}", root), salts))
+        }
+        _ => None,
+    };
+
+    // On-chain submissions are only accepted once the request is mined, and keep
+    // its request id so the approval can be matched to the same request.
+    let onchain_request = match &payload.tx_hash {
+        Some(tx_hash) => {
+            let client = pool_client(&state, &pool).ok_or_else(|| {
+                ApiError::invalid(
+                    "tx_hash",
+                    "on-chain submission is not available for this pool",
+                )
+            })?;
+            let tx_hash = parse_tx_hash("tx_hash", tx_hash)?;
+            let event = client
+                .certificate_requested(tx_hash)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::invalid(
+                        "tx_hash",
+                        "transaction is not a mined certificate request on this contract",
+                    )
+                })?;
+
+            if format!("{:#x}", event.certificator) != wallet {
+                return Err(ApiError::invalid(
+                    "tx_hash",
+                    "request was submitted by a different wallet",
+                ));
+            }
+            if format!("{:#x}", event.recipient) != payload.recipient_wallet.to_lowercase() {
+                return Err(ApiError::invalid(
+                    "recipient_wallet",
+                    "does not match the on-chain request",
+                ));
+            }
+            if event.certificate_hash != payload.document_hash {
+                return Err(ApiError::invalid(
+                    "document_hash",
+                    "does not match the on-chain request",
+                ));
+            }
 
-    if exists.0 > 0 {
-        return Err(ApiError::BadRequest("Certificate already submitted".into()));
+            // The request must be for this pool's institution, on chain too.
+            let institution = pool_institution(&state.db, &pool).await?;
+            if event.institution_id != institution.institution_id {
+                return Err(ApiError::invalid(
+                    "tx_hash",
+                    "request was made for a different institution",
+                ));
+            }
+            if let Some(pool_id) = pool.onchain_pool_id {
+                let onchain_pool = client.pool(U256::from(pool_id)).await?;
+                if onchain_pool.institution_id != event.institution_id {
+                    return Err(ApiError::invalid(
+                        "tx_hash",
+                        format!("request does not belong to on-chain pool {}", pool_id),
+                    ));
+                }
+            }
+
+            Some((
+                onchain_id("tx_hash", event.request_id)?,
+                format!("{:#x}", tx_hash),
+            ))
+        }
+        None => None,
+    };
+
+    let mut tx = state.db.begin().await?;
+
+    // Holds off a concurrent deactivation until the certificate is in.
+    let active: Option<(i32,)> =
+        sqlx::query_as("SELECT id FROM pools WHERE id = $1 AND is_active = true FOR SHARE")
+            .bind(pool.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    if active.is_none() {
+        return Err(ApiError::BadRequest("Pool not found or inactive".into()));
     }
 
-    let cert: Certificate = sqlx::query_as(
+    let cert = insert_certificate(
+        &mut tx,
+        &pool,
+        &wallet,
+        &payload,
+        onchain_request.as_ref(),
+        fields,
+        commitment.as_ref(),
+    )
+    .await?
+    .ok_or_else(|| ApiError::Conflict("Certificate already submitted".into()))?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Certificate submitted successfully",
+        "certificate": {
+            "id": cert.id,
+            "document_hash": cert.document_hash,
+            "status": cert.status,
+            "onchain_pool_id": cert.onchain_pool_id,
+            "onchain_request_id": cert.onchain_request_id,
+            "fields": cert.fields,
+            "fields_root": cert.fields_root
+        }
+    })))
+}
+
+/// Template fields of a submission to `pool`, validated and normalized.
+pub fn template_fields(
+    pool: &Pool,
+    fields: Option<&Map<String, Value>>,
+) -> Result<Option<Map<String, Value>>, ApiError> {
+    match (&pool.certificate_template, fields) {
+        (Some(template), fields) => Ok(Some(
+            validate_fields(template, fields.cloned().unwrap_or_default())
+                .map_err(ApiError::Validation)?,
+        )),
+        (None, Some(_)) => Err(ApiError::invalid(
+            "fields",
+            "this pool has no certificate template",
+        )),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Inserts a submitted certificate with the commitment to its fields, `None`
+/// when its document hash is already taken, including by a submission that
+/// is still being committed.
+pub async fn insert_certificate(
+    conn: &mut PgConnection,
+    pool: &Pool,
+    wallet: &str,
+    payload: &SubmitCertificateRequest,
+    onchain_request: Option<&(i64, String)>,
+    fields: Option<Map<String, Value>>,
+    commitment: Option<&(String, Map<String, Value>)>,
+) -> Result<Option<Certificate>, sqlx::Error> {
+    sqlx::query_as(
         r#"
         INSERT INTO certificates (
             pool_id, certificator_wallet, recipient_name, recipient_wallet,
-            certificate_type, document_hash, metadata_uri
+            certificate_type, document_hash, metadata_uri, onchain_pool_id,
+            onchain_request_id, request_tx_hash, fields, fields_root, field_salts
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        ON CONFLICT (document_hash) DO NOTHING
         RETURNING *
     "#,
     )
     .bind(pool.id)
-    .bind(&wallet)
+    .bind(wallet)
     .bind(&payload.recipient_name)
-    .bind(&payload.recipient_wallet.to_lowercase())
+    .bind(payload.recipient_wallet.to_lowercase())
     .bind(&payload.certificate_type)
     .bind(&payload.document_hash)
     .bind(&payload.metadata_uri)
-    .fetch_one(&state.db)
+    .bind(pool.onchain_pool_id)
+    .bind(onchain_request.map(|(request_id, _)| *request_id))
+    .bind(onchain_request.map(|(_, tx_hash)| tx_hash))
+    .bind(fields.map(Value::Object))
+    .bind(commitment.map(|(root, _)| root))
+    .bind(commitment.map(|(_, salts)| Value::Object(salts.clone())))
+    .fetch_optional(conn)
     .await
-    .map_err(|_| ApiError::Internal)?;
+}
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": "Certificate submitted successfully",
-        "certificate": {
-            "id": cert.id,
-            "document_hash": cert.document_hash,
-            "status": cert.status
-        }
-    })))
+/// Marks a pending certificate minted, `None` when it was decided in the
+/// meantime.
+pub async fn mint_pending_certificate(
+    conn: &mut PgConnection,
+    id: i32,
+    tx_hash: &str,
+    token_id: i32,
+) -> Result<Option<Certificate>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        UPDATE certificates 
+        SET status = 'minted', tx_hash = $1, token_id = $2, 
+            validated_at = $3, minted_at = $3
+        WHERE id = $4 AND status = 'pending'
+        RETURNING *
+    "#,
+    )
+    .bind(tx_hash)
+    .bind(token_id)
+    .bind(Utc::now())
+    .bind(id)
+    .fetch_optional(conn)
+    .await
+}
+
+/// Rejects a pending certificate, `None` when it was decided in the meantime.
+pub async fn reject_pending_certificate(
+    conn: &mut PgConnection,
+    id: i32,
+    rejection_reason: Option<&str>,
+) -> Result<Option<Certificate>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        UPDATE certificates 
+        SET status = 'rejected', rejection_reason = $1, validated_at = $2
+        WHERE id = $3 AND status = 'pending'
+        RETURNING *
+    "#,
+    )
+    .bind(rejection_reason)
+    .bind(Utc::now())
+    .bind(id)
+    .fetch_optional(conn)
+    .await
 }
 
 #[get("/pools/{code}/certificates")]
@@ -83,8 +295,7 @@ pub async fn list_pool_certificates(
     let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE code = $1")
         .bind(&code)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
         .ok_or(ApiError::NotFound)?;
 
     if user.auth_type == "email" {
@@ -102,14 +313,12 @@ pub async fn list_pool_certificates(
         .bind(pool.id)
         .bind(status)
         .fetch_all(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
     } else {
         sqlx::query_as("SELECT * FROM certificates WHERE pool_id = $1 ORDER BY created_at DESC")
             .bind(pool.id)
             .fetch_all(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?
+            .await?
     };
 
     Ok(HttpResponse::Ok().json(certificates))
@@ -119,6 +328,7 @@ pub async fn list_pool_certificates(
 pub async fn decide_certificate(
     state: web::Data<AppState>,
     user: AuthUser,
+    client: ClientInfo,
     path: web::Path<i32>,
     payload: web::Json<CertificateDecisionRequest>,
 ) -> Result<impl Responder, ApiError> {
@@ -134,23 +344,22 @@ pub async fn decide_certificate(
     let cert: Certificate = sqlx::query_as("SELECT * FROM certificates WHERE id = $1")
         .bind(cert_id)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
         .ok_or(ApiError::NotFound)?;
 
     if cert.status != "pending" {
-        return Err(ApiError::BadRequest("Certificate already processed".into()));
+        return Err(ApiError::Conflict("Certificate already processed".into()));
     }
 
     let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
         .bind(cert.pool_id)
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
     if pool.validator_id != user_id {
         return Err(ApiError::Forbidden);
     }
+    ensure_two_factor(&state.db, user_id).await?;
 
     if payload.approve {
         let tx_hash = payload
@@ -161,21 +370,62 @@ pub async fn decide_certificate(
             .token_id
             .ok_or_else(|| ApiError::BadRequest("token_id required for approval".into()))?;
 
-        sqlx::query(
-            r#"
-            UPDATE certificates 
-            SET status = 'minted', tx_hash = $1, token_id = $2, 
-                validated_at = $3, minted_at = $3
-            WHERE id = $4
-        "#,
+        // Requests submitted on chain must be approved by minting that same
+        // request, which the mint transaction proves.
+        if let Some(request_id) = cert.onchain_request_id {
+            // Without the pool's chain the mint cannot be proven, so the
+            // approval waits until it is configured again.
+            let client = pool_client(&state, &pool).ok_or_else(|| {
+                ApiError::Upstream("the chain this pool issues on is not configured".into())
+            })?;
+            let minted = client
+                .certificate_minted(parse_tx_hash("tx_hash", tx_hash)?)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::invalid(
+                        "tx_hash",
+                        "transaction did not mint a certificate on this contract",
+                    )
+                })?;
+            if onchain_id("tx_hash", minted.request_id)? != request_id {
+                return Err(ApiError::invalid(
+                    "tx_hash",
+                    "minted certificate belongs to a different request",
+                ));
+            }
+            if onchain_id("token_id", minted.token_id)? != token_id as i64 {
+                return Err(ApiError::invalid(
+                    "token_id",
+                    "does not match the minted token",
+                ));
+            }
+        }
+
+        // Only one of several concurrent decisions finds it still pending.
+        let mut tx = state.db.begin().await?;
+
+        mint_pending_certificate(&mut tx, cert_id, tx_hash, token_id)
+            .await?
+            .ok_or_else(|| ApiError::Conflict("Certificate already processed".into()))?;
+
+        audit::record(
+            &mut tx,
+            &state,
+            &user,
+            &client,
+            AuditEntry::new("certificate.decide", "certificate", cert_id).change(
+                serde_json::json!({ "status": cert.status }),
+                serde_json::json!({
+                    "status": "minted",
+                    "tx_hash": tx_hash,
+                    "token_id": token_id,
+                    "fields_root": cert.fields_root
+                }),
+            ),
         )
-        .bind(tx_hash)
-        .bind(token_id)
-        .bind(Utc::now())
-        .bind(cert_id)
-        .execute(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
+
+        tx.commit().await?;
 
         Ok(HttpResponse::Ok().json(serde_json::json!({
             "message": "Certificate approved and minted",
@@ -184,19 +434,28 @@ pub async fn decide_certificate(
             "tx_hash": tx_hash
         })))
     } else {
-        sqlx::query(
-            r#"
-            UPDATE certificates 
-            SET status = 'rejected', rejection_reason = $1, validated_at = $2
-            WHERE id = $3
-        "#,
+        let mut tx = state.db.begin().await?;
+
+        reject_pending_certificate(&mut tx, cert_id, payload.rejection_reason.as_deref())
+            .await?
+            .ok_or_else(|| ApiError::Conflict("Certificate already processed".into()))?;
+
+        audit::record(
+            &mut tx,
+            &state,
+            &user,
+            &client,
+            AuditEntry::new("certificate.decide", "certificate", cert_id).change(
+                serde_json::json!({ "status": cert.status }),
+                serde_json::json!({
+                    "status": "rejected",
+                    "rejection_reason": payload.rejection_reason
+                }),
+            ),
         )
-        .bind(&payload.rejection_reason)
-        .bind(Utc::now())
-        .bind(cert_id)
-        .execute(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
+
+        tx.commit().await?;
 
         Ok(HttpResponse::Ok().json(serde_json::json!({
             "message": "Certificate rejected",
@@ -223,19 +482,18 @@ pub async fn my_certificates(
     )
     .bind(&wallet)
     .fetch_all(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    .await?;
 
     let mut results = Vec::new();
     for cert in certificates {
         let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
             .bind(cert.pool_id)
             .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
         results.push(serde_json::json!({
             "certificate": cert,
+            "chain": pool_chain(&state.config, &pool),
             "pool_name": pool.name,
             "pool_code": pool.code
         }));
@@ -244,58 +502,394 @@ pub async fn my_certificates(
     Ok(HttpResponse::Ok().json(results))
 }
 
+/// Fields where the contract disagrees with a minted certificate in the DB.
+pub fn chain_mismatches(
+    cert: &Certificate,
+    institution_id: &str,
+    onchain: &OnChainCertificate,
+) -> Vec<&'static str> {
+    let mut mismatches = Vec::new();
+    if cert.token_id.map(|id| U256::from(id as u64)) != Some(onchain.token_id) {
+        mismatches.push("token_id");
+    }
+    if cert.recipient_wallet.to_lowercase() != format!("{:#x}", onchain.recipient) {
+        mismatches.push("recipient_wallet");
+    }
+    if institution_id != onchain.institution_id {
+        mismatches.push("institution_id");
+    }
+    mismatches
+}
+
+/// Looks the hash up on the pool's contract, or on every configured chain
+/// when the DB has no pool for it.
+async fn find_onchain(
+    state: &AppState,
+    pool: Option<&Pool>,
+    hash: &str,
+) -> Result<Option<(Arc<ChainClient>, OnChainCertificate)>, ApiError> {
+    let clients: Vec<&Arc<ChainClient>> = match pool {
+        Some(pool) => pool_client(state, pool).into_iter().collect(),
+        None => state.chains.iter().collect(),
+    };
+    if clients.is_empty() {
+        return Err(ApiError::BadRequest(
+            "On-chain verification is not available for this certificate".into(),
+        ));
+    }
+
+    for client in clients {
+        if let Some(onchain) = client.certificate_by_hash(hash).await? {
+            return Ok(Some((client.clone(), onchain)));
+        }
+    }
+    Ok(None)
+}
+
 #[get("/certificates/verify/{hash}")]
 pub async fn verify_certificate(
     state: web::Data<AppState>,
+    client: ClientInfo,
     path: web::Path<String>,
+    query: web::Query<VerifyCertificateQuery>,
 ) -> Result<impl Responder, ApiError> {
     let hash = path.into_inner();
+    let source = query.source;
+    if query.receipt && state.receipt_signer.is_none() {
+        return Err(ApiError::BadRequest(
+            "Verification receipts are not enabled".into(),
+        ));
+    }
 
     let cert: Option<Certificate> =
         sqlx::query_as("SELECT * FROM certificates WHERE document_hash = $1 AND status = 'minted'")
             .bind(&hash)
             .fetch_optional(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
-    if let Some(cert) = cert {
-        let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
-            .bind(cert.pool_id)
-            .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+    let record = match cert {
+        Some(cert) => {
+            let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
+                .bind(cert.pool_id)
+                .fetch_one(&state.db)
+                .await?;
 
-        let validator_req: ValidatorRequest = sqlx::query_as(
-            "SELECT * FROM validator_requests WHERE user_id = $1 AND status = 'approved' LIMIT 1",
-        )
-        .bind(pool.validator_id)
-        .fetch_one(&state.db)
-        .await
+            let institution = pool_institution(&state.db, &pool).await?;
+
+            Some((cert, pool, institution))
+        }
+        None => None,
+    };
+
+    // Name guesses are limited per client and certificate, so guessing one
+    // recipient's name cannot lock out everyone else verifying it.
+    if query.name.is_some() {
+        limit_key(
+            &state,
+            &format!(
+                "verify:{}:{}",
+                client.ip.as_deref().unwrap_or("unknown"),
+                hash.to_lowercase()
+            ),
+        )?;
+    }
+
+    // What the pool lets anyone holding the hash see, whatever the source. In
+    // challenge mode the verifier must already know the recipient's name to
+    // get a match.
+    let mut public = None;
+    if let Some((cert, pool, _)) = &record {
+        let mut privacy = pool
+            .privacy
+            .as_ref()
+            .map(|privacy| privacy.0.clone())
+            .unwrap_or_default();
+        if privacy.require_name_challenge {
+            let matched = query
+                .name
+                .as_deref()
+                .is_some_and(|name| name_matches(&cert.recipient_name, name));
+            if !matched {
+                return Ok(challenge_response(source));
+            }
+            privacy.show_recipient_name = true;
+        }
+        public = Some(public_view(cert, &privacy));
+    }
+
+    if source == VerifySource::Db {
+        return Ok(match (record, public) {
+            (Some((_, pool, institution)), Some((cert, redacted))) => {
+                let receipt = match query.receipt {
+                    true => Some(sign_receipt(&state, &cert, &pool, &institution).await?),
+                    false => None,
+                };
+                HttpResponse::Ok().json(serde_json::json!({
+                    "valid": true,
+                    "source": "db",
+                    "certificate": verified_certificate_json(&cert, &redacted),
+                    "chain": pool_chain(&state.config, &pool),
+                    "issuer": issuer_json(&pool, &institution),
+                    "receipt": receipt
+                }))
+            }
+            // Unknown hashes answer like a challenge, so a pool's challenge
+            // mode does not reveal which hashes exist.
+            _ => challenge_response(source),
+        });
+    }
+
+    let onchain = find_onchain(&state, record.as_ref().map(|(_, pool, _)| pool), &hash).await?;
+
+    let hide_owner = public
+        .as_ref()
+        .is_some_and(|(_, redacted)| redacted.contains(&"recipient_wallet"));
+    let onchain_json = onchain.as_ref().map(|(client, onchain)| {
+        let mut json = serde_json::json!({
+            "chain_id": client.chain_id,
+            "network": client.name,
+            "contract_address": client.contract_address.map(|a| format!("{:#x}", a)),
+            "token_id": onchain.token_id.to_string(),
+            "owner": format!("{:#x}", onchain.recipient),
+            "institution_id": onchain.institution_id,
+            "minted_at": onchain.minted_at.to_string()
+        });
+        if hide_owner {
+            if let Some(object) = json.as_object_mut() {
+                object.remove("owner");
+            }
+        }
+        json
+    });
+
+    if source == VerifySource::Chain {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "valid": onchain.is_some(),
+            "source": "chain",
+            "onchain": onchain_json
+        })));
+    }
+    if record.is_none() && onchain.is_none() {
+        return Ok(challenge_response(source));
+    }
+
+    let mismatches = match (&record, &onchain) {
+        (Some((cert, _, institution)), Some((_, onchain))) => {
+            chain_mismatches(cert, &institution.institution_id, onchain)
+        }
+        (Some(_), None) => vec!["missing_on_chain"],
+        (None, Some(_)) => vec!["missing_in_db"],
+        (None, None) => Vec::new(),
+    };
+    if !mismatches.is_empty() {
+        tracing::warn!(
+            document_hash = %hash,
+            ?mismatches,
+            "certificate differs between database and chain"
+        );
+    }
+
+    let valid = record.is_some() && onchain.is_some() && mismatches.is_empty();
+    let receipt = match (&record, &public, valid && query.receipt) {
+        (Some((_, pool, institution)), Some((cert, _)), true) => {
+            Some(sign_receipt(&state, cert, pool, institution).await?)
+        }
+        _ => None,
+    };
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "valid": valid,
+        "source": "both",
+        "certificate": public
+            .as_ref()
+            .map(|(cert, redacted)| verified_certificate_json(cert, redacted)),
+        "chain": record.as_ref().and_then(|(_, pool, _)| pool_chain(&state.config, pool)),
+        "issuer": record
+            .as_ref()
+            .map(|(_, pool, institution)| issuer_json(pool, institution)),
+        "onchain": onchain_json,
+        "mismatches": mismatches,
+        "receipt": receipt
+    })))
+}
+
+/// Answer to a verification that did not match a certificate: either the
+/// hash is unknown or its pool needs the recipient's name.
+fn challenge_response(source: VerifySource) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "valid": false,
+        "source": source,
+        "challenge_required": true,
+        "message": "Certificate not found, or supply the recipient's name to verify it"
+    }))
+}
+
+/// Signs a receipt for a minted certificate. The mint block is looked up on
+/// the pool's chain and left at 0 when the RPC cannot provide it. A receipt
+/// vouches for every value in it, so views with hidden recipient details,
+/// blank in `cert`, get none.
+async fn sign_receipt(
+    state: &AppState,
+    cert: &Certificate,
+    pool: &Pool,
+    institution: &Institution,
+) -> Result<SignedReceipt, ApiError> {
+    let signer = state
+        .receipt_signer
+        .as_ref()
+        .ok_or_else(|| ApiError::BadRequest("Verification receipts are not enabled".into()))?;
+    if cert.recipient_name.is_empty() || cert.recipient_wallet.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Receipts are not available while recipient details are hidden".into(),
+        ));
+    }
+    let recipient_wallet = cert
+        .recipient_wallet
+        .parse()
         .map_err(|_| ApiError::Internal)?;
+    let (Some(chain_id), Some(contract_address)) = (
+        pool.chain_id,
+        pool.contract_address
+            .as_deref()
+            .and_then(|address| address.parse().ok()),
+    ) else {
+        return Err(ApiError::BadRequest(
+            "Certificate is not linked to a chain".into(),
+        ));
+    };
 
-        Ok(HttpResponse::Ok().json(serde_json::json!({
-            "valid": true,
-            "certificate": {
-                "recipient_name": cert.recipient_name,
-                "recipient_wallet": cert.recipient_wallet,
-                "certificate_type": cert.certificate_type,
-                "document_hash": cert.document_hash,
-                "token_id": cert.token_id,
-                "tx_hash": cert.tx_hash,
-                "minted_at": cert.minted_at
-            },
-            "issuer": {
-                "institution_name": validator_req.institution_name,
-                "institution_id": validator_req.institution_id,
-                "pool_name": pool.name
+    let mut block_number = 0;
+    if let (Some(client), Some(tx_hash)) = (pool_client(state, pool), &cert.tx_hash) {
+        if let Ok(tx_hash) = parse_tx_hash("tx_hash", tx_hash) {
+            if let Ok(Some(receipt)) = client.receipt(tx_hash).await {
+                block_number = receipt.block_number.map_or(0, |block| block.as_u64());
             }
-        })))
-    } else {
-        Ok(HttpResponse::Ok().json(serde_json::json!({
+        }
+    }
+
+    let receipt = VerificationReceipt {
+        document_hash: cert.document_hash.clone(),
+        recipient_name: cert.recipient_name.clone(),
+        recipient_wallet,
+        certificate_type: cert.certificate_type.clone(),
+        institution_id: institution.institution_id.clone(),
+        institution_name: institution.name.clone(),
+        token_id: cert.token_id.map_or(0, |id| id as u64),
+        chain_id: chain_id as u64,
+        contract_address,
+        block_number,
+        issued_at: Utc::now().timestamp() as u64,
+    };
+
+    signer.sign(receipt).map_err(|e| {
+        tracing::error!(error = %e, "failed to sign verification receipt");
+        ApiError::Internal
+    })
+}
+
+/// Checks a receipt's signature and whether this backend issued it. `valid`
+/// only holds for receipts signed by our key; `signature_valid` reports a
+/// well-formed signature by any key.
+#[post("/verify/receipt")]
+pub async fn check_receipt(
+    state: web::Data<AppState>,
+    payload: web::Json<SignedReceipt>,
+) -> Result<impl Responder, ApiError> {
+    let signed = payload.into_inner();
+    let issuer = state.receipt_signer.as_ref().map(|signer| signer.address());
+
+    Ok(HttpResponse::Ok().json(match recover_signer(&signed) {
+        Ok(signer) => {
+            let trusted = issuer.is_some_and(|issuer| verify_receipt(&signed, issuer).is_ok());
+            let mut body = serde_json::json!({
+                "valid": trusted,
+                "signature_valid": true,
+                "signer": signer,
+                "receipt": signed.receipt
+            });
+            if !trusted {
+                body["message"] = "receipt was not signed by this backend".into();
+            }
+            body
+        }
+        Err(e) => serde_json::json!({
             "valid": false,
-            "message": "Certificate not found or not yet minted"
-        })))
+            "signature_valid": false,
+            "message": e.to_string()
+        }),
+    }))
+}
+
+/// Normalized comparison for the name challenge: case, surrounding and
+/// repeated whitespace are ignored.
+pub fn name_matches(expected: &str, supplied: &str) -> bool {
+    let normalize = |name: &str| {
+        name.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+    !supplied.trim().is_empty() && normalize(expected) == normalize(supplied)
+}
+
+/// Copy of `cert` with the details `privacy` hides blanked out, and the
+/// names of the hidden keys.
+pub fn public_view(cert: &Certificate, privacy: &PoolPrivacy) -> (Certificate, Vec<&'static str>) {
+    let mut public = cert.clone();
+    let mut redacted = Vec::new();
+    if !privacy.show_recipient_name {
+        public.recipient_name = String::new();
+        redacted.push("recipient_name");
+    }
+    if !privacy.show_recipient_wallet {
+        public.recipient_wallet = String::new();
+        redacted.push("recipient_wallet");
+    }
+    // Committed fields are only shown as disclosed by the recipient.
+    if !privacy.show_fields || cert.fields_root.is_some() {
+        public.fields = None;
+        redacted.push("fields");
     }
+    (public, redacted)
+}
+
+fn verified_certificate_json(cert: &Certificate, redacted: &[&str]) -> serde_json::Value {
+    let mut json = full_certificate_json(cert);
+    if let Some(object) = json.as_object_mut() {
+        for key in redacted {
+            object.remove(*key);
+        }
+        if !redacted.is_empty() {
+            object.insert("redacted".into(), serde_json::json!(redacted));
+        }
+    }
+    json
+}
+
+pub fn full_certificate_json(cert: &Certificate) -> serde_json::Value {
+    serde_json::json!({
+        "recipient_name": cert.recipient_name,
+        "recipient_wallet": cert.recipient_wallet,
+        "certificate_type": cert.certificate_type,
+        "fields": cert.fields,
+        "fields_root": cert.fields_root,
+        "document_hash": cert.document_hash,
+        "token_id": cert.token_id,
+        "tx_hash": cert.tx_hash,
+        "minted_at": cert.minted_at
+    })
+}
+
+pub fn issuer_json(pool: &Pool, institution: &Institution) -> serde_json::Value {
+    serde_json::json!({
+        "institution_name": institution.name,
+        "institution_id": institution.institution_id,
+        "description": institution.description,
+        "logo_url": institution.logo_url,
+        "website": institution.website,
+        "verified_domains": institution.verified_domains,
+        "pool_name": pool.name
+    })
 }
 
 #[get("/stats")]
@@ -303,19 +897,16 @@ pub async fn public_stats(state: web::Data<AppState>) -> Result<impl Responder,
     let total_validators: (i64,) =
         sqlx::query_as("SELECT COUNT(*) FROM validator_requests WHERE status = 'approved'")
             .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
     let total_pools: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM pools")
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
     let total_certificates: (i64,) =
         sqlx::query_as("SELECT COUNT(*) FROM certificates WHERE status = 'minted'")
             .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "total_validators": total_validators.0,
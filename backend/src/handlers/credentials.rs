@@ -0,0 +1,224 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::Utc;
+
+use crate::credentials::{
+    certificate_credential, credential_certificate_id, credential_jwt_claims,
+    open_badge_credential, sign_jwt, verify_jwt,
+};
+use crate::errors::ApiError;
+use crate::handlers::institutions::pool_institution;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::receipt::ReceiptSigner;
+use crate::state::AppState;
+
+fn credential_signer(state: &AppState) -> Result<&ReceiptSigner, ApiError> {
+    state
+        .receipt_signer
+        .as_deref()
+        .ok_or_else(|| ApiError::BadRequest("Credential export is not enabled".into()))
+}
+
+/// Loads a minted certificate for export. The holder, the certificator who
+/// submitted it and the pool's validator may export; everyone else verifies
+/// by hash.
+async fn exportable_certificate(
+    state: &AppState,
+    user: &AuthUser,
+    id: i32,
+) -> Result<(Certificate, Pool, Institution), ApiError> {
+    let cert: Certificate = sqlx::query_as("SELECT * FROM certificates WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
+        .bind(cert.pool_id)
+        .fetch_one(&state.db)
+        .await?;
+
+    let allowed = match user.auth_type.as_str() {
+        "wallet" => {
+            let wallet = user.sub.to_lowercase();
+            wallet == cert.recipient_wallet.to_lowercase() || wallet == cert.certificator_wallet
+        }
+        _ => user.sub.parse::<i32>().ok() == Some(pool.validator_id),
+    };
+    if !allowed {
+        return Err(ApiError::Forbidden);
+    }
+    if cert.status != "minted" {
+        return Err(ApiError::Conflict("Certificate is not minted yet".into()));
+    }
+
+    let institution = pool_institution(&state.db, &pool).await?;
+
+    Ok((cert, pool, institution))
+}
+
+#[get("/certificates/{id}/vc")]
+pub async fn export_credential(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    path: web::Path<i32>,
+) -> Result<impl Responder, ApiError> {
+    let signer = credential_signer(&state)?;
+    let (cert, pool, institution) =
+        exportable_certificate(&state, &user, path.into_inner()).await?;
+
+    let claims = certificate_credential(
+        &state.config.public_base_url,
+        signer.address(),
+        &cert,
+        &pool,
+        &institution,
+        Utc::now(),
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "format": "jwt_vc",
+        "jwt": sign_jwt(signer, &claims),
+        "credential": claims["vc"]
+    })))
+}
+
+/// Signed Open Badges 3.0 credential for one certificate.
+fn open_badge_json(
+    state: &AppState,
+    signer: &ReceiptSigner,
+    cert: &Certificate,
+    pool: &Pool,
+    institution: &Institution,
+) -> serde_json::Value {
+    let credential = open_badge_credential(
+        &state.config.public_base_url,
+        signer.address(),
+        cert,
+        pool,
+        institution,
+        Utc::now(),
+    );
+    serde_json::json!({
+        "certificate_id": cert.id,
+        "format": "open_badges_v3",
+        "jwt": sign_jwt(signer, &credential_jwt_claims(&credential)),
+        "credential": credential
+    })
+}
+
+#[get("/certificates/{id}/openbadge")]
+pub async fn export_open_badge(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    path: web::Path<i32>,
+) -> Result<impl Responder, ApiError> {
+    let signer = credential_signer(&state)?;
+    let (cert, pool, institution) =
+        exportable_certificate(&state, &user, path.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(open_badge_json(&state, signer, &cert, &pool, &institution)))
+}
+
+/// Open Badges for every minted certificate of a pool, for its validator.
+#[get("/pools/{code}/openbadges")]
+pub async fn export_pool_open_badges(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    path: web::Path<String>,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "email" {
+        return Err(ApiError::BadRequest(
+            "Validators must use email login".into(),
+        ));
+    }
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    let signer = credential_signer(&state)?;
+
+    let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE code = $1")
+        .bind(path.into_inner().to_uppercase())
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if pool.validator_id != user_id {
+        return Err(ApiError::Forbidden);
+    }
+
+    let institution = pool_institution(&state.db, &pool).await?;
+
+    let certificates: Vec<Certificate> = sqlx::query_as(
+        "SELECT * FROM certificates WHERE pool_id = $1 AND status = 'minted' ORDER BY minted_at",
+    )
+    .bind(pool.id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let badges: Vec<_> = certificates
+        .iter()
+        .map(|cert| open_badge_json(&state, signer, cert, &pool, &institution))
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!(
+                "etched-{}-openbadges.json",
+                pool.code.to_lowercase()
+            ))],
+        })
+        .json(serde_json::json!({
+            "pool_code": pool.code,
+            "count": badges.len(),
+            "badges": badges
+        })))
+}
+
+/// Verifies a JWT-VC or Open Badges credential issued by this backend and
+/// whether its certificate is still minted.
+#[post("/vc/verify")]
+pub async fn verify_credential(
+    state: web::Data<AppState>,
+    payload: web::Json<VerifyCredentialRequest>,
+) -> Result<impl Responder, ApiError> {
+    let signer = credential_signer(&state)?;
+
+    let claims = match verify_jwt(&payload.jwt, signer.verifying_key()) {
+        Ok(claims) => claims,
+        Err(e) => {
+            return Ok(HttpResponse::Ok().json(serde_json::json!({
+                "valid": false,
+                "message": e.to_string()
+            })))
+        }
+    };
+
+    // JWT-VCs wrap the credential in `vc`, Open Badges use it as the payload.
+    let credential = match claims.get("vc") {
+        Some(vc) => vc.clone(),
+        None => claims,
+    };
+    let Some(certificate_id) = credential["id"]
+        .as_str()
+        .and_then(|id| credential_certificate_id(&state.config.public_base_url, id))
+    else {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "valid": false,
+            "message": "credential was not issued by this backend"
+        })));
+    };
+
+    let minted: (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM certificates WHERE id = $1 AND status = 'minted'")
+            .bind(certificate_id)
+            .fetch_one(&state.db)
+            .await?;
+    let revoked = minted.0 == 0;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "valid": !revoked,
+        "revoked": revoked,
+        "issuer": credential["issuer"],
+        "credential": credential
+    })))
+}
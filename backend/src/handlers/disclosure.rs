@@ -0,0 +1,214 @@
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::Utc;
+use ethers_core::types::H256;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde_json::{Map, Value};
+
+use crate::disclosure::{disclose, fields_root, random_salts, verify_disclosure};
+use crate::errors::ApiError;
+use crate::handlers::certificates::template_fields;
+use crate::handlers::institutions::pool_institution;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::state::AppState;
+
+const COMMITMENT_PURPOSE: &str = "fields_commitment";
+/// Long enough to write the root into the metadata and mine the request.
+const COMMITMENT_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// Signs the salts drawn for `wallet`'s submission to pool `pool_code`.
+pub fn issue_commitment_token(
+    secret: &str,
+    wallet: &str,
+    pool_code: &str,
+    field_salts: Map<String, Value>,
+) -> Result<String, ApiError> {
+    let claims = CommitmentClaims {
+        sub: wallet.to_lowercase(),
+        purpose: COMMITMENT_PURPOSE.into(),
+        pool_code: pool_code.into(),
+        field_salts,
+        exp: (Utc::now().timestamp() + COMMITMENT_TTL_SECS) as usize,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .map_err(|_| ApiError::Internal)
+}
+
+/// Salts of a commitment token issued to `wallet` for pool `pool_code`.
+pub fn decode_commitment_token(
+    secret: &str,
+    token: &str,
+    wallet: &str,
+    pool_code: &str,
+) -> Result<Map<String, Value>, ApiError> {
+    let invalid = || ApiError::invalid("commitment_token", "is invalid or expired");
+    let claims = decode::<CommitmentClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| invalid())?
+    .claims;
+
+    if claims.purpose != COMMITMENT_PURPOSE
+        || claims.sub != wallet.to_lowercase()
+        || claims.pool_code != pool_code
+    {
+        return Err(invalid());
+    }
+    Ok(claims.field_salts)
+}
+
+/// Salts and root for a submission's fields, for certificators that write
+/// the root into the document metadata before submitting. The salts go back
+/// with the submission inside `commitment_token`.
+#[post("/pools/{code}/certificates/commitment")]
+pub async fn commit_fields(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    path: web::Path<String>,
+    payload: web::Json<FieldsCommitmentRequest>,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "wallet" {
+        return Err(ApiError::BadRequest(
+            "Certificators must use wallet login".into(),
+        ));
+    }
+
+    let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE code = $1 AND is_active = true")
+        .bind(path.into_inner().to_uppercase())
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Pool not found or inactive".into()))?;
+
+    let fields = template_fields(&pool, payload.fields.as_ref())?
+        .filter(|fields| !fields.is_empty())
+        .ok_or_else(|| ApiError::invalid("fields", "there are no fields to commit to"))?;
+    let salts = random_salts(&fields);
+    let root = fields_root(&fields, &salts).ok_or(ApiError::Internal)?;
+    let commitment_token = issue_commitment_token(
+        &state.config.jwt_secret,
+        &user.sub,
+        &pool.code,
+        salts.clone(),
+    )?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "fields": fields,
+        "fields_root": format!("{:#x}", root),
+        "field_salts": salts,
+        "commitment_token": commitment_token
+    })))
+}
+
+/// Proofs for a subset of a minted certificate's fields, for its recipient to
+/// hand to a verifier.
+#[get("/certificates/{id}/disclosure")]
+pub async fn disclose_fields(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    path: web::Path<i32>,
+    query: web::Query<DisclosureQuery>,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "wallet" {
+        return Err(ApiError::BadRequest(
+            "Recipients must use wallet login".into(),
+        ));
+    }
+
+    let cert: Certificate = sqlx::query_as("SELECT * FROM certificates WHERE id = $1")
+        .bind(path.into_inner())
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if cert.recipient_wallet.to_lowercase() != user.sub.to_lowercase() {
+        return Err(ApiError::Forbidden);
+    }
+
+    let (Some(fields), Some(salts)) = (
+        cert.fields.as_ref().and_then(|fields| fields.as_object()),
+        cert.field_salts
+            .as_ref()
+            .and_then(|salts| salts.as_object()),
+    ) else {
+        return Err(ApiError::Conflict(
+            "Certificate has no committed fields".into(),
+        ));
+    };
+
+    let mut disclosed = Vec::new();
+    for name in query
+        .fields
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+    {
+        let field = disclose(fields, salts, name)
+            .ok_or_else(|| ApiError::invalid("fields", format!("unknown field {}", name)))?;
+        disclosed.push(field);
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "document_hash": cert.document_hash,
+        "fields_root": cert.fields_root,
+        "disclosed": disclosed
+    })))
+}
+
+/// Checks disclosed fields against the root committed for a minted
+/// certificate.
+#[post("/certificates/verify/disclosure")]
+pub async fn verify_disclosed_fields(
+    state: web::Data<AppState>,
+    payload: web::Json<VerifyDisclosureRequest>,
+) -> Result<impl Responder, ApiError> {
+    let cert: Option<Certificate> =
+        sqlx::query_as("SELECT * FROM certificates WHERE document_hash = $1 AND status = 'minted'")
+            .bind(&payload.document_hash)
+            .fetch_optional(&state.db)
+            .await?;
+
+    let Some((cert, root)) = cert.and_then(|cert| {
+        let root: H256 = cert.fields_root.as_deref()?.parse().ok()?;
+        Some((cert, root))
+    }) else {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "valid": false,
+            "message": "Certificate not found or has no committed fields"
+        })));
+    };
+
+    let (verified, failed): (Vec<_>, Vec<_>) = payload
+        .disclosed
+        .iter()
+        .partition(|field| verify_disclosure(root, field));
+
+    let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
+        .bind(cert.pool_id)
+        .fetch_one(&state.db)
+        .await?;
+    let institution = pool_institution(&state.db, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "valid": failed.is_empty() && !verified.is_empty(),
+        "fields_root": cert.fields_root,
+        "fields": verified
+            .iter()
+            .map(|field| (field.name.clone(), field.value.clone()))
+            .collect::<serde_json::Map<_, _>>(),
+        "failed": failed.iter().map(|field| &field.name).collect::<Vec<_>>(),
+        "certificate_type": cert.certificate_type,
+        "minted_at": cert.minted_at,
+        "issuer": {
+            "institution_name": institution.name,
+            "institution_id": institution.institution_id,
+            "pool_name": pool.name
+        }
+    })))
+}
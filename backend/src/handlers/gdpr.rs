@@ -0,0 +1,423 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{get, post, web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+
+use crate::audit::{self, AuditEntry, ClientInfo};
+use crate::errors::ApiError;
+use crate::handlers::account::generate_token;
+use crate::handlers::two_factor::ensure_two_factor;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::state::AppState;
+
+/// Replaces erased recipient names. Hashes, wallets and on-chain ids stay so
+/// certificates keep verifying.
+pub const ERASED_NAME: &str = "[erased]";
+
+pub fn erased_email(user_id: i32) -> String {
+    format!("erased-{}@erased.invalid", user_id)
+}
+
+pub fn erased_username(user_id: i32) -> String {
+    format!("erased-{}", user_id)
+}
+
+/// Email user or wallet a request is about, with the wallet linked to the
+/// user if any.
+struct Subject {
+    user: Option<User>,
+    wallet: Option<String>,
+}
+
+async fn load_subject(state: &AppState, user: &AuthUser) -> Result<Subject, ApiError> {
+    if user.auth_type == "wallet" {
+        return Ok(Subject {
+            user: None,
+            wallet: Some(user.sub.to_lowercase()),
+        });
+    }
+
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    let db_user: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let wallet = db_user.wallet_address.as_ref().map(|w| w.to_lowercase());
+
+    Ok(Subject {
+        user: Some(db_user),
+        wallet,
+    })
+}
+
+/// Everything stored about the caller, as a downloadable JSON archive.
+/// Certificates the caller submitted as certificator are listed without
+/// their recipients' details. Audit events the caller performed or that
+/// target their account are included; they are kept unchanged after an
+/// erasure, as the accountability record for privileged actions.
+#[get("/me/export")]
+pub async fn export_my_data(
+    state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<impl Responder, ApiError> {
+    let subject = load_subject(&state, &user).await?;
+    let user_id = subject.user.as_ref().map(|u| u.id);
+
+    let validator_requests: Vec<ValidatorRequest> =
+        sqlx::query_as("SELECT * FROM validator_requests WHERE user_id = $1 ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(&state.db)
+            .await?;
+
+    let pools: Vec<Pool> =
+        sqlx::query_as("SELECT * FROM pools WHERE validator_id = $1 ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(&state.db)
+            .await?;
+
+    let certificates: Vec<Certificate> = sqlx::query_as(
+        "SELECT * FROM certificates WHERE LOWER(recipient_wallet) = $1 ORDER BY created_at",
+    )
+    .bind(&subject.wallet)
+    .fetch_all(&state.db)
+    .await?;
+
+    let submitted_certificates: Vec<(i32, i32, String, String, DateTime<Utc>)> = sqlx::query_as(
+        r#"
+            SELECT id, pool_id, document_hash, status, created_at FROM certificates
+            WHERE certificator_wallet = $1
+            ORDER BY created_at
+        "#,
+    )
+    .bind(&subject.wallet)
+    .fetch_all(&state.db)
+    .await?;
+    let submitted_certificates: Vec<_> = submitted_certificates
+        .into_iter()
+        .map(|(id, pool_id, document_hash, status, created_at)| {
+            serde_json::json!({
+                "id": id,
+                "pool_id": pool_id,
+                "document_hash": document_hash,
+                "status": status,
+                "created_at": created_at
+            })
+        })
+        .collect();
+
+    let share_links: Vec<ShareLink> = sqlx::query_as(
+        r#"
+        SELECT s.* FROM share_links s
+        JOIN certificates c ON c.id = s.certificate_id
+        WHERE LOWER(c.recipient_wallet) = $1
+        ORDER BY s.created_at
+    "#,
+    )
+    .bind(&subject.wallet)
+    .fetch_all(&state.db)
+    .await?;
+
+    let erasure_requests: Vec<ErasureRequest> = sqlx::query_as(
+        "SELECT * FROM erasure_requests WHERE user_id = $1 OR wallet = $2 ORDER BY created_at",
+    )
+    .bind(user_id)
+    .bind(&subject.wallet)
+    .fetch_all(&state.db)
+    .await?;
+
+    // Actors are stored as the user id or the lowercased wallet. Where
+    // someone else acted on the account, their IP and user agent stay out.
+    let actors: Vec<String> = user_id
+        .map(|id| id.to_string())
+        .into_iter()
+        .chain(subject.wallet.clone())
+        .collect();
+    let mut audit_events: Vec<AuditEvent> = sqlx::query_as(
+        r#"
+        SELECT * FROM audit_events
+        WHERE actor = ANY($1) OR (target_type = 'user' AND target_id = $2)
+        ORDER BY id
+    "#,
+    )
+    .bind(&actors)
+    .bind(user_id.map(|id| id.to_string()))
+    .fetch_all(&state.db)
+    .await?;
+    for event in &mut audit_events {
+        if !actors.contains(&event.actor) {
+            event.ip = None;
+            event.user_agent = None;
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("etched-export.json".into())],
+        })
+        .json(serde_json::json!({
+            "exported_at": Utc::now(),
+            "subject": {
+                "auth_type": user.auth_type,
+                "wallet": subject.wallet
+            },
+            "user": subject.user,
+            "validator_requests": validator_requests,
+            "pools": pools,
+            "certificates": certificates,
+            "submitted_certificates": submitted_certificates,
+            "share_links": share_links,
+            "erasure_requests": erasure_requests,
+            "audit_events": audit_events
+        })))
+}
+
+/// Asks for the caller's personal data to be erased, pending admin approval.
+#[post("/me/erasure")]
+pub async fn request_erasure(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    payload: Option<web::Json<CreateErasureRequest>>,
+) -> Result<impl Responder, ApiError> {
+    let subject = load_subject(&state, &user).await?;
+    let user_id = subject.user.as_ref().map(|u| u.id);
+    // Wallet logins are erased by wallet; email users by account, which
+    // covers their linked wallet too.
+    let wallet = match user_id {
+        Some(_) => None,
+        None => subject.wallet,
+    };
+
+    let pending: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM erasure_requests
+        WHERE status = 'pending' AND (user_id = $1 OR wallet = $2)
+    "#,
+    )
+    .bind(user_id)
+    .bind(&wallet)
+    .fetch_one(&state.db)
+    .await?;
+    if pending.0 > 0 {
+        return Err(ApiError::Conflict(
+            "An erasure request is already pending".into(),
+        ));
+    }
+
+    let request: ErasureRequest = sqlx::query_as(
+        r#"
+        INSERT INTO erasure_requests (user_id, wallet, reason)
+        VALUES ($1, $2, $3)
+        RETURNING *
+    "#,
+    )
+    .bind(user_id)
+    .bind(&wallet)
+    .bind(payload.and_then(|p| p.into_inner().reason))
+    .fetch_one(&state.db)
+    .await?;
+
+    tracing::info!(erasure_request_id = request.id, "erasure requested");
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "message": "Erasure request submitted for review",
+        "request": request
+    })))
+}
+
+#[get("/admin/erasure-requests")]
+pub async fn list_erasure_requests(
+    state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<impl Responder, ApiError> {
+    if user.role != "admin" {
+        return Err(ApiError::Forbidden);
+    }
+
+    let requests: Vec<ErasureRequest> = sqlx::query_as(
+        "SELECT * FROM erasure_requests WHERE status = 'pending' ORDER BY created_at ASC",
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(requests))
+}
+
+/// Pseudonymizes the off-chain personal fields of an erasure subject and
+/// returns how many rows of each kind were touched. Audit events are
+/// append-only and kept as they are under the operator's legitimate interest
+/// in an accountable record of privileged actions.
+pub async fn erase_subject(
+    tx: &mut Transaction<'_, Postgres>,
+    request: &ErasureRequest,
+) -> Result<serde_json::Value, ApiError> {
+    let mut wallet = request.wallet.clone();
+    let mut users = 0;
+    let mut validator_requests = 0;
+
+    if let Some(user_id) = request.user_id {
+        let linked: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT wallet_address FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(&mut **tx)
+                .await?;
+        wallet = linked.and_then(|(w,)| w).map(|w| w.to_lowercase());
+
+        // A random hash nobody knows the password for.
+        let password_hash =
+            bcrypt::hash(generate_token(), bcrypt::DEFAULT_COST).map_err(|_| ApiError::Internal)?;
+        users = sqlx::query(
+            r#"
+            UPDATE users
+            SET email = $1, username = $2, password_hash = $3, email_verified = false,
+                totp_secret = NULL, totp_enabled = false
+            WHERE id = $4
+        "#,
+        )
+        .bind(erased_email(user_id))
+        .bind(erased_username(user_id))
+        .bind(password_hash)
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        for table in ["user_tokens", "recovery_codes"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1", table))
+                .bind(user_id)
+                .execute(&mut **tx)
+                .await?;
+        }
+
+        validator_requests =
+            sqlx::query("UPDATE validator_requests SET document_url = NULL WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&mut **tx)
+                .await?
+                .rows_affected();
+    }
+
+    // Disclosure salts go with the fields, so the committed root can no
+    // longer be opened; the root itself stays as a hash.
+    let certificates = sqlx::query(
+        r#"
+        UPDATE certificates
+        SET recipient_name = $1, fields = NULL, field_salts = NULL
+        WHERE LOWER(recipient_wallet) = $2
+    "#,
+    )
+    .bind(ERASED_NAME)
+    .bind(&wallet)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+
+    let share_links = sqlx::query(
+        r#"
+        UPDATE share_links SET revoked_at = NOW()
+        WHERE revoked_at IS NULL AND certificate_id IN (
+            SELECT id FROM certificates WHERE LOWER(recipient_wallet) = $1
+        )
+    "#,
+    )
+    .bind(&wallet)
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+
+    Ok(serde_json::json!({
+        "users": users,
+        "validator_requests": validator_requests,
+        "certificates": certificates,
+        "share_links_revoked": share_links
+    }))
+}
+
+#[post("/admin/erasure-requests/{id}/decision")]
+pub async fn decide_erasure_request(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    client: ClientInfo,
+    path: web::Path<i32>,
+    payload: web::Json<ErasureDecisionRequest>,
+) -> Result<impl Responder, ApiError> {
+    if user.role != "admin" {
+        return Err(ApiError::Forbidden);
+    }
+    let admin_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    ensure_two_factor(&state.db, admin_id).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    let request: ErasureRequest =
+        sqlx::query_as("SELECT * FROM erasure_requests WHERE id = $1 FOR UPDATE")
+            .bind(path.into_inner())
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(ApiError::NotFound)?;
+    if request.status != "pending" {
+        return Err(ApiError::Conflict(
+            "Erasure request already processed".into(),
+        ));
+    }
+    if request.user_id == Some(admin_id) {
+        return Err(ApiError::BadRequest(
+            "Admins cannot approve their own erasure".into(),
+        ));
+    }
+
+    let (status, result) = if payload.approve {
+        ("approved", Some(erase_subject(&mut tx, &request).await?))
+    } else {
+        ("rejected", None)
+    };
+
+    let previous_status = request.status;
+    let request: ErasureRequest = sqlx::query_as(
+        r#"
+        UPDATE erasure_requests
+        SET status = $1, reviewed_by = $2, reviewed_at = NOW(), rejection_reason = $3, result = $4
+        WHERE id = $5
+        RETURNING *
+    "#,
+    )
+    .bind(status)
+    .bind(admin_id)
+    .bind(&payload.rejection_reason)
+    .bind(&result)
+    .bind(request.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("erasure_request.decide", "erasure_request", request.id).change(
+            serde_json::json!({ "status": previous_status }),
+            serde_json::json!({
+                "status": status,
+                "rejection_reason": request.rejection_reason,
+                "result": request.result
+            }),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    tracing::info!(
+        erasure_request_id = request.id,
+        admin_id,
+        status,
+        "erasure request decided"
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": format!("Erasure request {}", status),
+        "request": request
+    })))
+}
@@ -0,0 +1,128 @@
+use actix_web::{get, web, HttpResponse, Responder};
+use futures::future::join_all;
+use serde_json::{json, Map, Value};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::chain::ChainClient;
+use crate::db::{self, SCHEMA_VERSION};
+use crate::state::AppState;
+
+/// Upper bound for each dependency check, so a hung dependency fails the probe
+/// instead of stalling it past the orchestrator's own timeout.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Liveness: the process is up and serving requests.
+#[get("/health")]
+pub async fn health() -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION")
+    }))
+}
+
+async fn with_timeout<T>(check: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {}s", CHECK_TIMEOUT.as_secs())))
+}
+
+async fn check_database(state: &AppState) -> Value {
+    let started = Instant::now();
+    let ping = with_timeout(async {
+        sqlx::query("SELECT 1")
+            .execute(&state.db)
+            .await
+            .map_err(|e| e.to_string())
+    })
+    .await;
+
+    match ping {
+        Ok(_) => json!({
+            "status": "ok",
+            "latency_ms": started.elapsed().as_millis() as u64
+        }),
+        Err(error) => json!({ "status": "error", "error": error }),
+    }
+}
+
+/// Newer schemas are additive, so pods still on the old code stay ready
+/// while a rolling deploy migrates.
+pub fn schema_status(found: Option<i32>) -> &'static str {
+    if found >= Some(SCHEMA_VERSION) {
+        "ok"
+    } else {
+        "error"
+    }
+}
+
+async fn check_schema(state: &AppState) -> Value {
+    let found = with_timeout(async {
+        db::schema_version(&state.db)
+            .await
+            .map_err(|e| e.to_string())
+    })
+    .await;
+
+    match found {
+        Ok(found) => json!({
+            "status": schema_status(found),
+            "expected": SCHEMA_VERSION,
+            "found": found
+        }),
+        Err(error) => json!({ "status": "error", "expected": SCHEMA_VERSION, "error": error }),
+    }
+}
+
+async fn check_chain(chain: &ChainClient) -> Value {
+    let chain_id =
+        with_timeout(async { chain.rpc_chain_id().await.map_err(|e| e.to_string()) }).await;
+
+    match chain_id {
+        Ok(chain_id) => json!({
+            "status": if chain_id == chain.chain_id { "ok" } else { "error" },
+            "chain_id": chain_id,
+            "expected_chain_id": chain.chain_id
+        }),
+        Err(error) => json!({ "status": "error", "error": error }),
+    }
+}
+
+/// True when no check reported an error. Skipped checks do not count.
+pub fn is_ready(checks: &Map<String, Value>) -> bool {
+    checks
+        .values()
+        .all(|check| check["status"] == "ok" || check["status"] == "skipped")
+}
+
+/// Readiness: Postgres answers, its schema matches this build and every
+/// configured RPC node is on its expected chain. Answers 503 otherwise.
+#[get("/health/ready")]
+pub async fn readiness(state: web::Data<AppState>) -> impl Responder {
+    let chains: Vec<_> = state.chains.iter().cloned().collect();
+    let (database, schema, chain_checks) = tokio::join!(
+        check_database(&state),
+        check_schema(&state),
+        join_all(chains.iter().map(|chain| check_chain(chain)))
+    );
+
+    let mut checks = Map::new();
+    checks.insert("database".into(), database);
+    checks.insert("schema".into(), schema);
+    for (chain, check) in chains.iter().zip(chain_checks) {
+        checks.insert(format!("chain:{}", chain.name), check);
+    }
+
+    let ready = is_ready(&checks);
+    let body = json!({
+        "status": if ready { "ready" } else { "degraded" },
+        "version": env!("CARGO_PKG_VERSION"),
+        "checks": checks
+    });
+
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
@@ -0,0 +1,329 @@
+use actix_web::{get, post, put, web, HttpResponse, Responder};
+use sqlx::PgPool;
+
+use crate::audit::{self, AuditEntry, ClientInfo};
+use crate::errors::{ApiError, FieldError};
+use crate::handlers::pools::pool_chain;
+use crate::handlers::two_factor::ensure_two_factor;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::state::AppState;
+
+/// Normalizes a domain entered by an admin or validator (`@Uni.EDU ` -> `uni.edu`).
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_start_matches('@').to_lowercase();
+
+    let valid = domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+
+    valid.then_some(domain)
+}
+
+/// Whether the domain of `email` is one of `domains` or a subdomain of one.
+pub fn email_domain_matches(email: &str, domains: &[String]) -> bool {
+    let email = email.trim().to_lowercase();
+    let Some((_, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+
+    domains
+        .iter()
+        .any(|d| domain == *d || domain.ends_with(&format!(".{}", d)))
+}
+
+fn normalize_domains(domains: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut normalized = Vec::new();
+    for domain in domains {
+        let domain = normalize_domain(domain).ok_or_else(|| {
+            ApiError::invalid("verified_domains", format!("Invalid domain: {}", domain))
+        })?;
+        if !normalized.contains(&domain) {
+            normalized.push(domain);
+        }
+    }
+    Ok(normalized)
+}
+
+pub async fn find_institution(
+    db: &PgPool,
+    institution_id: &str,
+) -> Result<Option<Institution>, ApiError> {
+    sqlx::query_as("SELECT * FROM institutions WHERE institution_id = $1")
+        .bind(institution_id)
+        .fetch_optional(db)
+        .await
+        .map_err(ApiError::from)
+}
+
+/// Institution of an approved validator, if the validator has one.
+pub async fn validator_institution(
+    db: &PgPool,
+    user_id: i32,
+) -> Result<Option<Institution>, ApiError> {
+    sqlx::query_as(
+        r#"
+        SELECT i.* FROM institutions i
+        JOIN validator_requests vr ON vr.institution_id = i.institution_id
+        WHERE vr.user_id = $1 AND vr.status = 'approved'
+        LIMIT 1
+    "#,
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+    .map_err(ApiError::from)
+}
+
+/// Institution `pool` issues certificates for.
+pub async fn pool_institution(db: &PgPool, pool: &Pool) -> Result<Institution, ApiError> {
+    sqlx::query_as("SELECT * FROM institutions WHERE id = $1")
+        .bind(pool.institution_id)
+        .fetch_optional(db)
+        .await?
+        .ok_or(ApiError::Internal)
+}
+
+#[get("/institutions")]
+pub async fn list_institutions(state: web::Data<AppState>) -> Result<impl Responder, ApiError> {
+    let institutions: Vec<Institution> =
+        sqlx::query_as("SELECT * FROM institutions ORDER BY name ASC")
+            .fetch_all(&state.db)
+            .await?;
+
+    Ok(HttpResponse::Ok().json(institutions))
+}
+
+#[post("/institutions")]
+pub async fn create_institution(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    client: ClientInfo,
+    payload: web::Json<CreateInstitutionRequest>,
+) -> Result<impl Responder, ApiError> {
+    if user.role != "admin" {
+        return Err(ApiError::Forbidden);
+    }
+    let admin_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    ensure_two_factor(&state.db, admin_id).await?;
+
+    let institution_id = payload.institution_id.trim();
+    let mut missing = Vec::new();
+    if institution_id.is_empty() {
+        missing.push(FieldError {
+            field: "institution_id".into(),
+            message: "is required".into(),
+        });
+    }
+    if payload.name.trim().is_empty() {
+        missing.push(FieldError {
+            field: "name".into(),
+            message: "is required".into(),
+        });
+    }
+    if !missing.is_empty() {
+        return Err(ApiError::Validation(missing));
+    }
+
+    if find_institution(&state.db, institution_id).await?.is_some() {
+        return Err(ApiError::Conflict("Institution already exists".into()));
+    }
+
+    let domains = normalize_domains(&payload.verified_domains)?;
+
+    let mut tx = state.db.begin().await?;
+
+    let institution: Institution = sqlx::query_as(
+        r#"
+        INSERT INTO institutions (institution_id, name, description, logo_url, website, verified_domains)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *
+    "#,
+    )
+    .bind(institution_id)
+    .bind(payload.name.trim())
+    .bind(&payload.description)
+    .bind(&payload.logo_url)
+    .bind(&payload.website)
+    .bind(&domains)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new(
+            "institution.create",
+            "institution",
+            &institution.institution_id,
+        )
+        .created(serde_json::json!(institution)),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(institution))
+}
+
+#[get("/institutions/{institution_id}")]
+pub async fn get_institution(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, ApiError> {
+    let institution = find_institution(&state.db, &path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let validators: Vec<User> = sqlx::query_as(
+        r#"
+        SELECT u.* FROM users u
+        JOIN validator_requests vr ON u.id = vr.user_id
+        WHERE vr.institution_id = $1 AND vr.status = 'approved'
+        ORDER BY vr.reviewed_at ASC
+    "#,
+    )
+    .bind(&institution.institution_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let validators: Vec<serde_json::Value> = validators
+        .into_iter()
+        .map(|u| {
+            serde_json::json!({
+                "username": u.username,
+                "wallet_address": u.wallet_address
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "institution": institution,
+        "validators": validators
+    })))
+}
+
+#[put("/institutions/{institution_id}")]
+pub async fn update_institution(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    client: ClientInfo,
+    path: web::Path<String>,
+    payload: web::Json<UpdateInstitutionRequest>,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "email" {
+        return Err(ApiError::Forbidden);
+    }
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    ensure_two_factor(&state.db, user_id).await?;
+
+    let institution = find_institution(&state.db, &path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if user.role != "admin" {
+        let own = validator_institution(&state.db, user_id).await?;
+        if own.map(|i| i.id) != Some(institution.id) {
+            return Err(ApiError::Forbidden);
+        }
+    }
+
+    // Verified domains vouch for every future applicant's email, so only
+    // admins may change them.
+    let domains = match &payload.verified_domains {
+        Some(_) if user.role != "admin" => return Err(ApiError::Forbidden),
+        Some(domains) => normalize_domains(domains)?,
+        None => institution.verified_domains.clone(),
+    };
+
+    let mut tx = state.db.begin().await?;
+
+    let updated: Institution = sqlx::query_as(
+        r#"
+        UPDATE institutions
+        SET name = $1, description = $2, logo_url = $3, website = $4, verified_domains = $5
+        WHERE id = $6
+        RETURNING *
+    "#,
+    )
+    .bind(payload.name.as_deref().unwrap_or(&institution.name))
+    .bind(
+        payload
+            .description
+            .as_ref()
+            .or(institution.description.as_ref()),
+    )
+    .bind(payload.logo_url.as_ref().or(institution.logo_url.as_ref()))
+    .bind(payload.website.as_ref().or(institution.website.as_ref()))
+    .bind(&domains)
+    .bind(institution.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("institution.update", "institution", &updated.institution_id)
+            .change(serde_json::json!(institution), serde_json::json!(updated)),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+#[get("/institutions/{institution_id}/pools")]
+pub async fn list_institution_pools(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, ApiError> {
+    let institution = find_institution(&state.db, &path.into_inner())
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let pools: Vec<Pool> = sqlx::query_as(
+        r#"
+        SELECT * FROM pools
+        WHERE institution_id = $1 AND is_active = true
+        ORDER BY created_at DESC
+    "#,
+    )
+    .bind(institution.id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut results = Vec::new();
+    for pool in pools {
+        let validator: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
+            .bind(pool.validator_id)
+            .fetch_one(&state.db)
+            .await?;
+
+        let chain = pool_chain(&state.config, &pool);
+        results.push(PoolResponse {
+            id: pool.id,
+            code: pool.code,
+            name: pool.name,
+            description: pool.description,
+            validator_name: validator.username,
+            institution_name: institution.name.clone(),
+            institution_id: institution.institution_id.clone(),
+            is_active: pool.is_active,
+            created_at: pool.created_at,
+            chain,
+            onchain_pool_id: pool.onchain_pool_id,
+            certificate_template: pool.certificate_template.map(|template| template.0),
+            privacy: pool.privacy.map(|privacy| privacy.0).unwrap_or_default(),
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(results))
+}
@@ -0,0 +1,28 @@
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+
+use crate::errors::ApiError;
+use crate::state::AppState;
+
+/// Prometheus scrape endpoint. Protected by `METRICS_TOKEN` when configured.
+#[get("/metrics")]
+pub async fn scrape_metrics(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<impl Responder, ApiError> {
+    if let Some(expected) = &state.config.metrics_token {
+        let provided = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "));
+        if provided != Some(expected.as_str()) {
+            return Err(ApiError::Unauthorized);
+        }
+    }
+
+    state.metrics.refresh(&state).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(state.metrics.render()))
+}
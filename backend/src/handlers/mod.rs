@@ -1,9 +1,29 @@
+pub mod account;
 pub mod admin;
+pub mod audit;
 pub mod auth;
 pub mod certificates;
+pub mod credentials;
+pub mod disclosure;
+pub mod gdpr;
+pub mod health;
+pub mod institutions;
+pub mod metrics;
 pub mod pools;
+pub mod sharing;
+pub mod two_factor;
 
+pub use account::*;
 pub use admin::*;
+pub use audit::*;
 pub use auth::*;
 pub use certificates::*;
+pub use credentials::*;
+pub use disclosure::*;
+pub use gdpr::*;
+pub use health::*;
+pub use institutions::*;
+pub use metrics::*;
 pub use pools::*;
+pub use sharing::*;
+pub use two_factor::*;
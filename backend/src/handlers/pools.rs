@@ -1,10 +1,19 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{get, post, put, web, HttpResponse, Responder};
+use ethers_core::types::{H256, U256};
 use rand::Rng;
+use sqlx::types::Json;
+use std::sync::Arc;
 
+use crate::audit::{self, AuditEntry, ClientInfo};
+use crate::chain::ChainClient;
+use crate::config::{format_eth, Config};
 use crate::errors::ApiError;
+use crate::handlers::institutions::{pool_institution, validator_institution};
+use crate::handlers::two_factor::ensure_two_factor;
 use crate::middleware::AuthUser;
 use crate::models::*;
 use crate::state::AppState;
+use crate::templates::validate_template;
 
 fn generate_pool_code() -> String {
     const CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
@@ -17,10 +26,47 @@ fn generate_pool_code() -> String {
         .collect()
 }
 
+/// Chain info for a pool, with the network name from config when the chain
+/// is still configured.
+pub fn pool_chain(config: &Config, pool: &Pool) -> Option<ChainInfo> {
+    let chain_id = pool.chain_id?;
+    Some(ChainInfo {
+        chain_id,
+        network: config
+            .chain(chain_id as u64)
+            .map(|chain| chain.name.clone()),
+        contract_address: pool.contract_address.clone(),
+    })
+}
+
+/// Client for the chain and contract a pool lives on, when that chain is
+/// reachable and still uses the same contract.
+pub fn pool_client<'a>(state: &'a AppState, pool: &Pool) -> Option<&'a Arc<ChainClient>> {
+    let client = state.chains.get(pool.chain_id? as u64)?;
+    let contract = client.contract_address.map(|a| format!("{:#x}", a));
+    (contract == pool.contract_address).then_some(client)
+}
+
+pub fn parse_tx_hash(field: &str, value: &str) -> Result<H256, ApiError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ApiError::invalid(field, "must be a 0x-prefixed 32-byte transaction hash"))
+}
+
+/// On-chain ids are uint256 but stored as BIGINT.
+pub fn onchain_id(field: &str, id: U256) -> Result<i64, ApiError> {
+    if id > U256::from(i64::MAX) {
+        return Err(ApiError::invalid(field, "on-chain id out of range"));
+    }
+    Ok(id.as_u64() as i64)
+}
+
 #[post("/pools")]
 pub async fn create_pool(
     state: web::Data<AppState>,
     user: AuthUser,
+    client: ClientInfo,
     payload: web::Json<CreatePoolRequest>,
 ) -> Result<impl Responder, ApiError> {
     if user.auth_type != "email" {
@@ -30,21 +76,16 @@ pub async fn create_pool(
     }
 
     let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    ensure_two_factor(&state.db, user_id).await?;
 
-    let validator_req: ValidatorRequest = sqlx::query_as(
-        "SELECT * FROM validator_requests WHERE user_id = $1 AND status = 'approved' LIMIT 1",
-    )
-    .bind(user_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?
-    .ok_or_else(|| ApiError::Forbidden)?;
+    let institution = validator_institution(&state.db, user_id)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
 
     let db_user: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
         .bind(user_id)
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
     if db_user.wallet_address.is_none() {
         return Err(ApiError::BadRequest(
@@ -52,13 +93,46 @@ pub async fn create_pool(
         ));
     }
 
+    let chain = match payload.chain_id {
+        Some(chain_id) => Some(
+            state
+                .config
+                .chain(chain_id)
+                .ok_or_else(|| ApiError::invalid("chain_id", "chain is not supported"))?,
+        ),
+        None => state.config.default_chain(),
+    };
+
+    // When the chain is reachable, the pool is only registered once its
+    // creation transaction is mined, and linked to the on-chain pool id.
+    let onchain_pool_id = match chain.and_then(|c| state.chains.get(c.chain_id)) {
+        Some(client) => {
+            let tx_hash = parse_tx_hash("tx_hash", &payload.tx_hash)?;
+            let event = client.pool_created(tx_hash).await?.ok_or_else(|| {
+                ApiError::invalid(
+                    "tx_hash",
+                    "transaction is not a mined pool creation on this contract",
+                )
+            })?;
+
+            let wallet = db_user.wallet_address.as_deref().unwrap_or_default();
+            if format!("{:#x}", event.validator) != wallet.to_lowercase() {
+                return Err(ApiError::invalid(
+                    "tx_hash",
+                    "pool was created by a different wallet",
+                ));
+            }
+            Some(onchain_id("tx_hash", event.pool_id)?)
+        }
+        None => None,
+    };
+
     let mut code = generate_pool_code();
     loop {
         let exists: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM pools WHERE code = $1")
             .bind(&code)
             .fetch_one(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
         if exists.0 == 0 {
             break;
@@ -66,21 +140,47 @@ pub async fn create_pool(
         code = generate_pool_code();
     }
 
+    let mut tx = state.db.begin().await?;
+
     let pool: Pool = sqlx::query_as(
         r#"
-        INSERT INTO pools (code, validator_id, name, description, tx_hash)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO pools (
+            code, validator_id, institution_id, name, description, tx_hash, chain_id,
+            contract_address, onchain_pool_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
         RETURNING *
     "#,
     )
     .bind(&code)
     .bind(user_id)
+    .bind(institution.id)
     .bind(&payload.name)
     .bind(&payload.description)
     .bind(&payload.tx_hash)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    .bind(chain.map(|c| c.chain_id as i64))
+    .bind(chain.and_then(|c| c.contract_address.as_deref()))
+    .bind(onchain_pool_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("pool.create", "pool", pool.id).created(serde_json::json!({
+            "code": pool.code,
+            "name": pool.name,
+            "institution_id": pool.institution_id,
+            "tx_hash": pool.tx_hash,
+            "chain_id": pool.chain_id,
+            "onchain_pool_id": pool.onchain_pool_id
+        })),
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Pool created successfully",
@@ -88,9 +188,12 @@ pub async fn create_pool(
             "id": pool.id,
             "code": pool.code,
             "name": pool.name,
-            "tx_hash": pool.tx_hash
+            "tx_hash": pool.tx_hash,
+            "onchain_pool_id": pool.onchain_pool_id,
+            "chain": pool_chain(&state.config, &pool)
         },
-        "institution_name": validator_req.institution_name
+        "institution_name": institution.name,
+        "institution_id": institution.institution_id
     })))
 }
 
@@ -104,33 +207,31 @@ pub async fn get_pool(
     let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE code = $1 AND is_active = true")
         .bind(&code)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
         .ok_or(ApiError::NotFound)?;
 
-    let validator_req: ValidatorRequest = sqlx::query_as(
-        "SELECT * FROM validator_requests WHERE user_id = $1 AND status = 'approved' LIMIT 1",
-    )
-    .bind(pool.validator_id)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| ApiError::Internal)?;
+    let institution = pool_institution(&state.db, &pool).await?;
 
     let validator: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
         .bind(pool.validator_id)
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
+    let chain = pool_chain(&state.config, &pool);
     Ok(HttpResponse::Ok().json(PoolResponse {
         id: pool.id,
         code: pool.code,
         name: pool.name,
         description: pool.description,
         validator_name: validator.username,
-        institution_name: validator_req.institution_name,
+        institution_name: institution.name,
+        institution_id: institution.institution_id,
         is_active: pool.is_active,
         created_at: pool.created_at,
+        chain,
+        onchain_pool_id: pool.onchain_pool_id,
+        certificate_template: pool.certificate_template.map(|template| template.0),
+        privacy: pool.privacy.map(|privacy| privacy.0).unwrap_or_default(),
     }))
 }
 
@@ -151,8 +252,7 @@ pub async fn my_pools(
         sqlx::query_as("SELECT * FROM pools WHERE validator_id = $1 ORDER BY created_at DESC")
             .bind(user_id)
             .fetch_all(&state.db)
-            .await
-            .map_err(|_| ApiError::Internal)?;
+            .await?;
 
     let mut results = Vec::new();
     for pool in pools {
@@ -161,18 +261,17 @@ pub async fn my_pools(
         )
         .bind(pool.id)
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
         let minted: (i64,) = sqlx::query_as(
             "SELECT COUNT(*) FROM certificates WHERE pool_id = $1 AND status = 'minted'",
         )
         .bind(pool.id)
         .fetch_one(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+        .await?;
 
         results.push(serde_json::json!({
+            "chain": pool_chain(&state.config, &pool),
             "pool": pool,
             "pending_certificates": pending.0,
             "minted_certificates": minted.0
@@ -186,6 +285,7 @@ pub async fn my_pools(
 pub async fn toggle_pool(
     state: web::Data<AppState>,
     user: AuthUser,
+    client: ClientInfo,
     path: web::Path<i32>,
 ) -> Result<impl Responder, ApiError> {
     if user.auth_type != "email" {
@@ -200,21 +300,37 @@ pub async fn toggle_pool(
     let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
         .bind(pool_id)
         .fetch_optional(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?
+        .await?
         .ok_or(ApiError::NotFound)?;
 
     if pool.validator_id != user_id {
         return Err(ApiError::Forbidden);
     }
+    ensure_two_factor(&state.db, user_id).await?;
 
-    let new_status = !pool.is_active;
-    sqlx::query("UPDATE pools SET is_active = $1 WHERE id = $2")
-        .bind(new_status)
-        .bind(pool_id)
-        .execute(&state.db)
-        .await
-        .map_err(|_| ApiError::Internal)?;
+    let mut tx = state.db.begin().await?;
+
+    // Flipped in place so concurrent toggles each take effect.
+    let (new_status,): (bool,) = sqlx::query_as(
+        "UPDATE pools SET is_active = NOT is_active WHERE id = $1 RETURNING is_active",
+    )
+    .bind(pool_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("pool.toggle", "pool", pool_id).change(
+            serde_json::json!({ "is_active": !new_status }),
+            serde_json::json!({ "is_active": new_status }),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": if new_status { "Pool activated" } else { "Pool deactivated" },
@@ -222,10 +338,140 @@ pub async fn toggle_pool(
     })))
 }
 
+/// Sets the fields certificates in the pool carry. An empty field list
+/// removes the template.
+#[put("/pools/{id}/template")]
+pub async fn set_pool_template(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    client: ClientInfo,
+    path: web::Path<i32>,
+    payload: web::Json<CertificateTemplate>,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "email" {
+        return Err(ApiError::BadRequest(
+            "Validators must use email login".into(),
+        ));
+    }
+
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    let pool_id = path.into_inner();
+    let template = payload.into_inner();
+
+    let errors = validate_template(&template);
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors));
+    }
+
+    let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
+        .bind(pool_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if pool.validator_id != user_id {
+        return Err(ApiError::Forbidden);
+    }
+    ensure_two_factor(&state.db, user_id).await?;
+
+    let template = (!template.fields.is_empty()).then_some(template);
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("UPDATE pools SET certificate_template = $1 WHERE id = $2")
+        .bind(template.clone().map(Json))
+        .bind(pool_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("pool.set_template", "pool", pool_id).change(
+            serde_json::json!({ "certificate_template": pool.certificate_template }),
+            serde_json::json!({ "certificate_template": template }),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Template updated",
+        "certificate_template": template
+    })))
+}
+
+#[put("/pools/{id}/privacy")]
+pub async fn set_pool_privacy(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    client: ClientInfo,
+    path: web::Path<i32>,
+    payload: web::Json<PoolPrivacy>,
+) -> Result<impl Responder, ApiError> {
+    if user.auth_type != "email" {
+        return Err(ApiError::BadRequest(
+            "Validators must use email login".into(),
+        ));
+    }
+
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    let pool_id = path.into_inner();
+    let privacy = payload.into_inner();
+
+    let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
+        .bind(pool_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if pool.validator_id != user_id {
+        return Err(ApiError::Forbidden);
+    }
+    ensure_two_factor(&state.db, user_id).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query("UPDATE pools SET privacy = $1 WHERE id = $2")
+        .bind(Json(&privacy))
+        .bind(pool_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("pool.set_privacy", "pool", pool_id).change(
+            serde_json::json!({ "privacy": pool.privacy }),
+            serde_json::json!({ "privacy": privacy }),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Privacy settings updated",
+        "privacy": privacy
+    })))
+}
+
 #[get("/pools/info")]
 pub async fn pool_info(state: web::Data<AppState>) -> Result<impl Responder, ApiError> {
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "admin_wallet": state.config.admin_wallet,
-        "pool_cost_eth": state.config.pool_cost_eth
+        "pool_cost_eth": format_eth(state.config.pool_cost_wei),
+        "pool_cost_wei": state.config.pool_cost_wei.to_string(),
+        "default_chain_id": state.config.default_chain_id,
+        "chains": state.config.chains.iter().map(|chain| serde_json::json!({
+            "chain_id": chain.chain_id,
+            "network": chain.name,
+            "contract_address": chain.contract_address,
+            "deployment_block": chain.deployment_block
+        })).collect::<Vec<_>>()
     })))
 }
@@ -0,0 +1,182 @@
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use chrono::{Duration, Utc};
+
+use crate::audit::{self, AuditEntry, ClientInfo};
+use crate::errors::ApiError;
+use crate::handlers::account::{generate_token, hash_token};
+use crate::handlers::certificates::{full_certificate_json, issuer_json};
+use crate::handlers::institutions::pool_institution;
+use crate::handlers::pools::pool_chain;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::state::AppState;
+
+const MAX_SHARE_LINK_DAYS: i64 = 365;
+
+/// Loads a minted certificate owned by the calling recipient wallet.
+async fn recipient_certificate(
+    state: &AppState,
+    user: &AuthUser,
+    id: i32,
+) -> Result<Certificate, ApiError> {
+    if user.auth_type != "wallet" {
+        return Err(ApiError::BadRequest(
+            "Recipients must use wallet login".into(),
+        ));
+    }
+
+    let cert: Certificate = sqlx::query_as("SELECT * FROM certificates WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    if cert.recipient_wallet.to_lowercase() != user.sub.to_lowercase() {
+        return Err(ApiError::Forbidden);
+    }
+    if cert.status != "minted" {
+        return Err(ApiError::Conflict("Certificate is not minted yet".into()));
+    }
+    Ok(cert)
+}
+
+#[post("/certificates/{id}/shares")]
+pub async fn create_share_link(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    path: web::Path<i32>,
+    payload: Option<web::Json<CreateShareLinkRequest>>,
+) -> Result<impl Responder, ApiError> {
+    let cert = recipient_certificate(&state, &user, path.into_inner()).await?;
+    let payload = payload.map(|p| p.into_inner()).unwrap_or_default();
+
+    let expires_at = match payload.expires_in_days {
+        Some(days) if !(1..=MAX_SHARE_LINK_DAYS).contains(&days) => {
+            return Err(ApiError::invalid(
+                "expires_in_days",
+                format!("must be between 1 and {}", MAX_SHARE_LINK_DAYS),
+            ))
+        }
+        Some(days) => Some(Utc::now() + Duration::days(days)),
+        None => None,
+    };
+
+    let token = generate_token();
+    let link: ShareLink = sqlx::query_as(
+        r#"
+        INSERT INTO share_links (certificate_id, token_hash, expires_at)
+        VALUES ($1, $2, $3)
+        RETURNING *
+    "#,
+    )
+    .bind(cert.id)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "share": link,
+        "token": token,
+        "url": format!("{}/share/{}", state.config.frontend_url, token)
+    })))
+}
+
+#[get("/certificates/{id}/shares")]
+pub async fn list_share_links(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    path: web::Path<i32>,
+) -> Result<impl Responder, ApiError> {
+    let cert = recipient_certificate(&state, &user, path.into_inner()).await?;
+
+    let links: Vec<ShareLink> = sqlx::query_as(
+        "SELECT * FROM share_links WHERE certificate_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(cert.id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(links))
+}
+
+#[delete("/certificates/{id}/shares/{share_id}")]
+pub async fn revoke_share_link(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    client: ClientInfo,
+    path: web::Path<(i32, i32)>,
+) -> Result<impl Responder, ApiError> {
+    let (id, share_id) = path.into_inner();
+    let cert = recipient_certificate(&state, &user, id).await?;
+
+    let mut tx = state.db.begin().await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE share_links SET revoked_at = NOW()
+        WHERE id = $1 AND certificate_id = $2 AND revoked_at IS NULL
+    "#,
+    )
+    .bind(share_id)
+    .bind(cert.id)
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("share_link.revoke", "share_link", share_id).change(
+            serde_json::json!({ "certificate_id": cert.id, "revoked": false }),
+            serde_json::json!({ "certificate_id": cert.id, "revoked": true }),
+        ),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Share link revoked"
+    })))
+}
+
+/// Full certificate details for the holder of a share link, regardless of
+/// the pool's privacy settings.
+#[get("/share/{token}")]
+pub async fn view_shared_certificate(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, ApiError> {
+    let cert: Certificate = sqlx::query_as(
+        r#"
+        SELECT c.* FROM certificates c
+        JOIN share_links s ON s.certificate_id = c.id
+        WHERE s.token_hash = $1 AND s.revoked_at IS NULL
+          AND (s.expires_at IS NULL OR s.expires_at > NOW())
+          AND c.status = 'minted'
+    "#,
+    )
+    .bind(hash_token(&path.into_inner()))
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let pool: Pool = sqlx::query_as("SELECT * FROM pools WHERE id = $1")
+        .bind(cert.pool_id)
+        .fetch_one(&state.db)
+        .await?;
+    let institution = pool_institution(&state.db, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "valid": true,
+        "certificate": full_certificate_json(&cert),
+        "chain": pool_chain(&state.config, &pool),
+        "issuer": issuer_json(&pool, &institution)
+    })))
+}
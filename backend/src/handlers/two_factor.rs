@@ -0,0 +1,466 @@
+use actix_web::{get, post, put, web, HttpResponse, Responder};
+use chrono::Utc;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
+use sqlx::PgPool;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::audit::{self, AuditEntry, ClientInfo};
+use crate::errors::ApiError;
+use crate::handlers::account::hash_token;
+use crate::handlers::auth::{email_login_response, is_locked, record_failed_login};
+use crate::middleware::rate_limit::limit_key;
+use crate::middleware::AuthUser;
+use crate::models::*;
+use crate::state::AppState;
+
+const TOTP_ISSUER: &str = "Etched";
+const CHALLENGE_PURPOSE: &str = "two_factor";
+const CHALLENGE_TTL_SECS: i64 = 5 * 60;
+const RECOVERY_CODE_COUNT: usize = 10;
+const REQUIRE_VALIDATOR_2FA_KEY: &str = "require_validator_2fa";
+const REQUIRE_ADMIN_2FA_KEY: &str = "require_admin_2fa";
+
+pub fn build_totp(secret: &str, account_name: &str) -> Result<TOTP, ApiError> {
+    let bytes = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|_| ApiError::Internal)?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        bytes,
+        Some(TOTP_ISSUER.into()),
+        account_name.into(),
+    )
+    .map_err(|_| ApiError::Internal)
+}
+
+/// Recovery codes look like `k7mq-x3vd` and are only shown once.
+pub fn generate_recovery_codes() -> Vec<String> {
+    const CHARSET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = (0..8)
+                .map(|_| CHARSET[rng.gen_range(0..CHARSET.len())] as char)
+                .collect();
+            format!("{}-{}", &code[..4], &code[4..])
+        })
+        .collect()
+}
+
+/// Time step whose code is `code`, looking as far around `now` as
+/// `check_current` does.
+pub fn matching_step(totp: &TOTP, code: &str, now: u64) -> Option<u64> {
+    let current = now / totp.step;
+    let skew = totp.skew as u64;
+    (current.saturating_sub(skew)..=current + skew).find(|step| {
+        let expected = totp.generate(step * totp.step);
+        expected.len() == code.len()
+            && expected
+                .bytes()
+                .zip(code.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    })
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+pub fn issue_challenge_token(secret: &str, user_id: i32) -> Result<String, ApiError> {
+    let claims = ChallengeClaims {
+        sub: user_id.to_string(),
+        purpose: CHALLENGE_PURPOSE.into(),
+        exp: (Utc::now().timestamp() + CHALLENGE_TTL_SECS) as usize,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .map_err(|_| ApiError::Internal)
+}
+
+pub fn decode_challenge_token(secret: &str, token: &str) -> Result<i32, ApiError> {
+    let claims = decode::<ChallengeClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| ApiError::Unauthorized)?
+    .claims;
+
+    if claims.purpose != CHALLENGE_PURPOSE {
+        return Err(ApiError::Unauthorized);
+    }
+
+    claims.sub.parse().map_err(|_| ApiError::Unauthorized)
+}
+
+pub async fn two_factor_policy(db: &PgPool) -> Result<TwoFactorPolicy, ApiError> {
+    let values: Vec<(String, String)> =
+        sqlx::query_as("SELECT key, value FROM settings WHERE key = ANY($1)")
+            .bind([REQUIRE_VALIDATOR_2FA_KEY, REQUIRE_ADMIN_2FA_KEY])
+            .fetch_all(db)
+            .await?;
+    let enabled = |key: &str| values.iter().any(|(k, v)| k == key && v == "true");
+
+    Ok(TwoFactorPolicy {
+        required_for_validators: enabled(REQUIRE_VALIDATOR_2FA_KEY),
+        required_for_admins: enabled(REQUIRE_ADMIN_2FA_KEY),
+    })
+}
+
+/// Rejects validator and admin actions while the policy requires 2FA for
+/// the user's role and the user has not enrolled yet.
+pub async fn ensure_two_factor(db: &PgPool, user_id: i32) -> Result<(), ApiError> {
+    let (role, enabled): (String, bool) =
+        sqlx::query_as("SELECT role, totp_enabled FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(db)
+            .await?;
+
+    if !enabled && two_factor_policy(db).await?.required_for(&role) {
+        return Err(ApiError::BadRequest(format!(
+            "Two-factor authentication must be enabled for {}s",
+            role
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a TOTP code, or consumes a recovery code when no TOTP code is given.
+async fn verify_second_factor(
+    db: &PgPool,
+    user: &User,
+    code: Option<&str>,
+    recovery_code: Option<&str>,
+) -> Result<(), ApiError> {
+    let secret = user
+        .totp_secret
+        .as_deref()
+        .ok_or_else(|| ApiError::BadRequest("Two-factor authentication is not set up".into()))?;
+
+    if let Some(code) = code {
+        let totp = build_totp(secret, &user.email)?;
+        let step = matching_step(&totp, code.trim(), Utc::now().timestamp() as u64)
+            .ok_or(ApiError::Unauthorized)?;
+
+        // A code is spent once used, and so is every earlier one, which
+        // also settles concurrent replays.
+        let fresh = sqlx::query(
+            r#"
+            UPDATE users SET totp_last_step = $1
+            WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+        "#,
+        )
+        .bind(step as i64)
+        .bind(user.id)
+        .execute(db)
+        .await?;
+
+        return if fresh.rows_affected() == 1 {
+            Ok(())
+        } else {
+            Err(ApiError::Unauthorized)
+        };
+    }
+
+    let recovery_code = recovery_code
+        .ok_or_else(|| ApiError::BadRequest("A code or recovery code is required".into()))?;
+
+    let consumed = sqlx::query(
+        r#"
+        UPDATE recovery_codes SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+    "#,
+    )
+    .bind(user.id)
+    .bind(hash_token(&normalize_recovery_code(recovery_code)))
+    .execute(db)
+    .await?;
+
+    if consumed.rows_affected() == 0 {
+        return Err(ApiError::Unauthorized);
+    }
+    Ok(())
+}
+
+async fn replace_recovery_codes(db: &PgPool, user_id: i32) -> Result<Vec<String>, ApiError> {
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+
+    let codes = generate_recovery_codes();
+    for code in &codes {
+        sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(hash_token(code))
+            .execute(db)
+            .await?;
+    }
+
+    Ok(codes)
+}
+
+async fn email_user(state: &AppState, user: &AuthUser) -> Result<User, ApiError> {
+    if user.auth_type != "email" {
+        return Err(ApiError::BadRequest(
+            "Two-factor authentication is only available for email accounts".into(),
+        ));
+    }
+
+    let user_id: i32 = user.sub.parse().map_err(|_| ApiError::Internal)?;
+    sqlx::query_as("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::NotFound)
+}
+
+#[post("/auth/2fa/verify")]
+pub async fn verify_two_factor(
+    state: web::Data<AppState>,
+    payload: web::Json<TwoFactorVerifyRequest>,
+) -> Result<impl Responder, ApiError> {
+    let user_id = decode_challenge_token(&state.config.jwt_secret, &payload.challenge_token)?;
+    limit_key(&state, &format!("2fa:{}", user_id))?;
+
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    if !user.totp_enabled || is_locked(&user) {
+        return Err(ApiError::Unauthorized);
+    }
+
+    // Wrong codes count toward the same lockout as wrong passwords.
+    match verify_second_factor(
+        &state.db,
+        &user,
+        payload.code.as_deref(),
+        payload.recovery_code.as_deref(),
+    )
+    .await
+    {
+        Err(ApiError::Unauthorized) => {
+            tracing::info!(user_id = user.id, "login failed: wrong second factor");
+            record_failed_login(&state, user.id).await?;
+            Err(ApiError::Unauthorized)
+        }
+        Err(err) => Err(err),
+        Ok(()) => email_login_response(&state, user).await,
+    }
+}
+
+#[post("/auth/2fa/setup")]
+pub async fn setup_two_factor(
+    state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<impl Responder, ApiError> {
+    let db_user = email_user(&state, &user).await?;
+
+    if db_user.totp_enabled {
+        return Err(ApiError::BadRequest(
+            "Two-factor authentication is already enabled".into(),
+        ));
+    }
+
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let totp = build_totp(&secret, &db_user.email)?;
+
+    sqlx::query("UPDATE users SET totp_secret = $1 WHERE id = $2")
+        .bind(&secret)
+        .bind(db_user.id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "secret": secret,
+        "otpauth_uri": totp.get_url(),
+        "message": "Scan the URI as a QR code, then confirm with a code at /auth/2fa/enable"
+    })))
+}
+
+#[post("/auth/2fa/enable")]
+pub async fn enable_two_factor(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    payload: web::Json<TwoFactorCodeRequest>,
+) -> Result<impl Responder, ApiError> {
+    let db_user = email_user(&state, &user).await?;
+
+    if db_user.totp_enabled {
+        return Err(ApiError::BadRequest(
+            "Two-factor authentication is already enabled".into(),
+        ));
+    }
+
+    let code = payload
+        .code
+        .as_deref()
+        .ok_or_else(|| ApiError::BadRequest("code is required".into()))?;
+    verify_second_factor(&state.db, &db_user, Some(code), None).await?;
+
+    sqlx::query("UPDATE users SET totp_enabled = true WHERE id = $1")
+        .bind(db_user.id)
+        .execute(&state.db)
+        .await?;
+
+    let recovery_codes = replace_recovery_codes(&state.db, db_user.id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Two-factor authentication enabled",
+        "recovery_codes": recovery_codes
+    })))
+}
+
+#[post("/auth/2fa/disable")]
+pub async fn disable_two_factor(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    payload: web::Json<TwoFactorCodeRequest>,
+) -> Result<impl Responder, ApiError> {
+    let db_user = email_user(&state, &user).await?;
+
+    if !db_user.totp_enabled {
+        return Err(ApiError::BadRequest(
+            "Two-factor authentication is not enabled".into(),
+        ));
+    }
+
+    if two_factor_policy(&state.db)
+        .await?
+        .required_for(&db_user.role)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Two-factor authentication is required for {}s",
+            db_user.role
+        )));
+    }
+
+    verify_second_factor(
+        &state.db,
+        &db_user,
+        payload.code.as_deref(),
+        payload.recovery_code.as_deref(),
+    )
+    .await?;
+
+    sqlx::query("UPDATE users SET totp_enabled = false, totp_secret = NULL WHERE id = $1")
+        .bind(db_user.id)
+        .execute(&state.db)
+        .await?;
+
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(db_user.id)
+        .execute(&state.db)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Two-factor authentication disabled"
+    })))
+}
+
+#[post("/auth/2fa/recovery-codes")]
+pub async fn regenerate_recovery_codes(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    payload: web::Json<TwoFactorCodeRequest>,
+) -> Result<impl Responder, ApiError> {
+    let db_user = email_user(&state, &user).await?;
+
+    if !db_user.totp_enabled {
+        return Err(ApiError::BadRequest(
+            "Two-factor authentication is not enabled".into(),
+        ));
+    }
+
+    let code = payload
+        .code
+        .as_deref()
+        .ok_or_else(|| ApiError::BadRequest("code is required".into()))?;
+    verify_second_factor(&state.db, &db_user, Some(code), None).await?;
+
+    let recovery_codes = replace_recovery_codes(&state.db, db_user.id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "recovery_codes": recovery_codes
+    })))
+}
+
+#[get("/admin/settings/two-factor")]
+pub async fn get_two_factor_policy(
+    state: web::Data<AppState>,
+    user: AuthUser,
+) -> Result<impl Responder, ApiError> {
+    if user.role != "admin" {
+        return Err(ApiError::Forbidden);
+    }
+
+    Ok(HttpResponse::Ok().json(two_factor_policy(&state.db).await?))
+}
+
+#[put("/admin/settings/two-factor")]
+pub async fn set_two_factor_policy(
+    state: web::Data<AppState>,
+    user: AuthUser,
+    client: ClientInfo,
+    payload: web::Json<TwoFactorPolicy>,
+) -> Result<impl Responder, ApiError> {
+    if user.role != "admin" {
+        return Err(ApiError::Forbidden);
+    }
+    let db_user = email_user(&state, &user).await?;
+    ensure_two_factor(&state.db, db_user.id).await?;
+
+    // Requiring it without having it would lock the admin out.
+    if payload.required_for_admins && !db_user.totp_enabled {
+        return Err(ApiError::BadRequest(
+            "Enable two-factor authentication before requiring it for admins".into(),
+        ));
+    }
+
+    let previous = two_factor_policy(&state.db).await?;
+    let mut tx = state.db.begin().await?;
+
+    for (key, value) in [
+        (REQUIRE_VALIDATOR_2FA_KEY, payload.required_for_validators),
+        (REQUIRE_ADMIN_2FA_KEY, payload.required_for_admins),
+    ] {
+        sqlx::query(
+            r#"
+            INSERT INTO settings (key, value, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()
+        "#,
+        )
+        .bind(key)
+        .bind(value.to_string())
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    audit::record(
+        &mut tx,
+        &state,
+        &user,
+        &client,
+        AuditEntry::new("settings.two_factor_policy", "settings", "two_factor")
+            .change(serde_json::json!(previous), serde_json::json!(payload.0)),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(payload.into_inner()))
+}
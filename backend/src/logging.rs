@@ -0,0 +1,35 @@
+use sha2::{Digest, Sha256};
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_FILTER: &str = "info,sqlx=warn";
+
+/// Installs the global subscriber. `format` is `json` for one JSON object per
+/// line, anything else for human-readable output. Levels come from `RUST_LOG`.
+pub fn init(format: &str) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let result = if format.eq_ignore_ascii_case("json") {
+        builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init()
+    } else {
+        builder.try_init()
+    };
+
+    if let Err(err) = result {
+        eprintln!("Logging already initialized: {}", err);
+    }
+}
+
+/// Stand-in for auth material (nonces, signatures, tokens) in logs: a short
+/// hash lets two log lines be correlated without revealing the value.
+pub fn redact(secret: &str) -> String {
+    let digest = Sha256::digest(secret.as_bytes());
+    let prefix: String = digest[..4].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("[redacted sha256:{}]", prefix)
+}
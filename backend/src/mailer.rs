@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::config::Config;
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, Error)]
+#[error("Mail delivery failed: {0}")]
+pub struct MailError(pub String);
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), MailError>;
+}
+
+/// Builds the mailer selected by `MAIL_BACKEND`.
+pub fn from_config(config: &Config) -> Result<Arc<dyn Mailer>, MailError> {
+    match config.mail_backend.as_str() {
+        "smtp" => Ok(Arc::new(SmtpMailer::new(config)?)),
+        "file" => Ok(Arc::new(FileMailer::new(
+            config.mail_from.clone(),
+            config.mail_dir.as_ref().map(PathBuf::from),
+        ))),
+        other => Err(MailError(format!("Unknown MAIL_BACKEND: {}", other))),
+    }
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &Config) -> Result<Self, MailError> {
+        let host = config
+            .smtp_host
+            .as_deref()
+            .ok_or_else(|| MailError("SMTP_HOST must be set for the smtp backend".into()))?;
+
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+            .map_err(|e| MailError(e.to_string()))?
+            .port(config.smtp_port);
+
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: config.mail_from.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        let message = Message::builder()
+            .from(
+                self.from
+                    .parse()
+                    .map_err(|_| MailError("Invalid MAIL_FROM".into()))?,
+            )
+            .to(email
+                .to
+                .parse()
+                .map_err(|_| MailError(format!("Invalid recipient: {}", email.to)))?)
+            .subject(email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body)
+            .map_err(|e| MailError(e.to_string()))?;
+
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| MailError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// Logs every message and, when a directory is configured, writes it there as a
+/// plain-text file. Meant for local development and tests.
+pub struct FileMailer {
+    from: String,
+    dir: Option<PathBuf>,
+}
+
+impl FileMailer {
+    pub fn new(from: String, dir: Option<PathBuf>) -> Self {
+        Self { from, dir }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        let contents = format!(
+            "From: {}\nTo: {}\nSubject: {}\n\n{}\n",
+            self.from, email.to, email.subject, email.body
+        );
+
+        match &self.dir {
+            Some(dir) => {
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .map_err(|e| MailError(e.to_string()))?;
+
+                let path = dir.join(format!(
+                    "{}-{}.txt",
+                    chrono::Utc::now().format("%Y%m%d%H%M%S"),
+                    uuid::Uuid::new_v4()
+                ));
+                tokio::fs::write(&path, contents)
+                    .await
+                    .map_err(|e| MailError(e.to_string()))?;
+
+                tracing::info!(to = %email.to, path = %path.display(), "mail written");
+            }
+            // Bodies carry reset and verification links, which must not
+            // end up in logs.
+            None => tracing::info!(
+                to = %email.to,
+                subject = %email.subject,
+                "mail not delivered, set MAIL_DIR to keep it"
+            ),
+        }
+
+        Ok(())
+    }
+}
@@ -1,31 +1,42 @@
+mod audit;
+mod chain;
 mod config;
+mod credentials;
 mod db;
+mod disclosure;
 mod errors;
 mod handlers;
+mod logging;
+mod mailer;
+mod metrics;
 mod middleware;
 mod models;
+mod receipt;
 mod state;
+mod templates;
 
 #[cfg(test)]
 mod tests;
 
-use actix_cors::Cors;
-use actix_web::{get, web::Data, App, HttpResponse, HttpServer, Responder};
+use actix_web::{
+    error::JsonPayloadError,
+    middleware::from_fn,
+    web::{Data, JsonConfig, PathConfig, QueryConfig},
+    App, HttpServer,
+};
 
-use config::Config;
+use config::{Config, Profile};
+use errors::ApiError;
 use state::AppState;
 
-#[get("/health")]
-async fn health() -> impl Responder {
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "ok",
-        "version": env!("CARGO_PKG_VERSION")
-    }))
-}
-
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let config = Config::from_env();
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    logging::init(&config.log_format);
+    tracing::info!(config = %config.redacted(), "effective configuration");
     let bind_addr = config.bind_addr.clone();
 
     let state = AppState::new(config).await;
@@ -34,41 +45,126 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to initialize database");
 
-    println!("   Etched Backend starting...");
-    println!("   Bind address: http://{}", bind_addr);
-    println!("   Admin wallet: {}", state.config.admin_wallet);
-    println!("   Pool cost: {} ETH", state.config.pool_cost_eth);
+    if let Some((email, password)) = state.config.bootstrap_admin() {
+        db::seed_admin(&state.db, email, password)
+            .await
+            .expect("Failed to seed admin user");
+    }
+
+    if let Some(chain) = state.config.default_chain() {
+        let updated =
+            db::backfill_pool_chains(&state.db, chain.chain_id, chain.contract_address.as_deref())
+                .await
+                .expect("Failed to assign pools to the default chain");
+        if updated > 0 {
+            tracing::info!(chain = %chain.name, pools = updated, "pools assigned to default chain");
+        }
+    }
+
+    for chain in state.chains.iter() {
+        match chain.verify_deployment().await {
+            Ok(total_pools) => {
+                tracing::info!(chain = %chain.name, %total_pools, "contract reachable")
+            }
+            Err(err) if state.config.profile == Profile::Production => {
+                tracing::error!(chain = %chain.name, error = %err, "contract check failed");
+                std::process::exit(1);
+            }
+            Err(err) => tracing::warn!(chain = %chain.name, error = %err, "contract check failed"),
+        }
+    }
+
+    tracing::info!(bind_addr = %bind_addr, profile = %state.config.profile, "Etched backend starting");
 
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allow_any_origin()
-            .allow_any_method()
-            .allow_any_header();
+        let json_limit = state.config.max_json_body_bytes;
 
         App::new()
             .app_data(Data::new(state.clone()))
-            .wrap(cors)
-            .service(health)
+            .app_data(JsonConfig::default().limit(json_limit).error_handler(
+                move |err, _| match err {
+                    JsonPayloadError::Overflow { .. }
+                    | JsonPayloadError::OverflowKnownLength { .. } => {
+                        ApiError::PayloadTooLarge(json_limit).into()
+                    }
+                    err => ApiError::BadRequest(err.to_string()).into(),
+                },
+            ))
+            .app_data(
+                QueryConfig::default()
+                    .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()),
+            )
+            .app_data(
+                PathConfig::default()
+                    .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()),
+            )
+            .wrap(from_fn(middleware::rate_limit::rate_limit))
+            .wrap(from_fn(middleware::request_log::request_log))
+            .wrap(from_fn(middleware::request_id::request_id))
+            .wrap(middleware::security::security_headers(&state.config))
+            .wrap(middleware::security::cors(
+                &state.config.cors_allowed_origins,
+            ))
+            .service(handlers::health)
+            .service(handlers::readiness)
+            .service(handlers::scrape_metrics)
             .service(handlers::login)
             .service(handlers::register)
             .service(handlers::get_nonce)
             .service(handlers::verify_wallet)
+            .service(handlers::verify_email)
+            .service(handlers::resend_verification_email)
+            .service(handlers::forgot_password)
+            .service(handlers::reset_password)
+            .service(handlers::change_password)
+            .service(handlers::verify_two_factor)
+            .service(handlers::setup_two_factor)
+            .service(handlers::enable_two_factor)
+            .service(handlers::disable_two_factor)
+            .service(handlers::regenerate_recovery_codes)
             .service(handlers::get_me)
+            .service(handlers::export_my_data)
+            .service(handlers::request_erasure)
             .service(handlers::connect_wallet)
             .service(handlers::list_validator_requests)
             .service(handlers::decide_validator_request)
             .service(handlers::list_validators)
             .service(handlers::admin_stats)
+            .service(handlers::list_erasure_requests)
+            .service(handlers::list_audit_events)
+            .service(handlers::verify_audit_chain)
+            .service(handlers::decide_erasure_request)
+            .service(handlers::get_two_factor_policy)
+            .service(handlers::set_two_factor_policy)
+            .service(handlers::list_institutions)
+            .service(handlers::create_institution)
+            .service(handlers::get_institution)
+            .service(handlers::update_institution)
+            .service(handlers::list_institution_pools)
             .service(handlers::pool_info)
             .service(handlers::my_pools)
             .service(handlers::create_pool)
             .service(handlers::toggle_pool)
+            .service(handlers::set_pool_template)
+            .service(handlers::set_pool_privacy)
             .service(handlers::get_pool)
+            .service(handlers::commit_fields)
             .service(handlers::submit_certificate)
             .service(handlers::list_pool_certificates)
             .service(handlers::decide_certificate)
             .service(handlers::my_certificates)
             .service(handlers::verify_certificate)
+            .service(handlers::check_receipt)
+            .service(handlers::disclose_fields)
+            .service(handlers::verify_disclosed_fields)
+            .service(handlers::create_share_link)
+            .service(handlers::list_share_links)
+            .service(handlers::revoke_share_link)
+            .service(handlers::view_shared_certificate)
+            .service(handlers::export_credential)
+            .service(handlers::export_open_badge)
+            .service(handlers::export_pool_open_badges)
+            .service(handlers::verify_credential)
             .service(handlers::public_stats)
     })
     .bind(&bind_addr)?
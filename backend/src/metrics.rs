@@ -0,0 +1,167 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+use crate::state::AppState;
+
+/// Route label for requests that matched no resource, so scanners probing
+/// random paths cannot blow up the label cardinality.
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_connections: IntGaugeVec,
+    db_max_connections: IntGauge,
+    nonce_store_size: IntGauge,
+    certificates: IntGaugeVec,
+    pools: IntGaugeVec,
+    pending_validator_requests: IntGauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("etched".into()), None).expect("valid metrics namespace");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .expect("valid metric");
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route"],
+        )
+        .expect("valid metric");
+        let db_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .expect("valid metric");
+        let db_max_connections = IntGauge::new(
+            "db_pool_max_connections",
+            "Maximum size of the database pool",
+        )
+        .expect("valid metric");
+        let nonce_store_size = IntGauge::new(
+            "nonce_store_size",
+            "Wallet login nonces waiting for a signature",
+        )
+        .expect("valid metric");
+        let certificates = IntGaugeVec::new(
+            Opts::new("certificates", "Certificates by status"),
+            &["status"],
+        )
+        .expect("valid metric");
+        let pools = IntGaugeVec::new(Opts::new("pools", "Pools by active flag"), &["active"])
+            .expect("valid metric");
+        let pending_validator_requests = IntGauge::new(
+            "pending_validator_requests",
+            "Validator requests waiting for an admin decision",
+        )
+        .expect("valid metric");
+
+        let collectors: Vec<Box<dyn prometheus::core::Collector>> = vec![
+            Box::new(http_requests.clone()),
+            Box::new(http_request_duration.clone()),
+            Box::new(db_connections.clone()),
+            Box::new(db_max_connections.clone()),
+            Box::new(nonce_store_size.clone()),
+            Box::new(certificates.clone()),
+            Box::new(pools.clone()),
+            Box::new(pending_validator_requests.clone()),
+        ];
+        for collector in collectors {
+            registry
+                .register(collector)
+                .expect("metric registered once");
+        }
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            db_connections,
+            db_max_connections,
+            nonce_store_size,
+            certificates,
+            pools,
+            pending_validator_requests,
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(seconds);
+    }
+
+    /// Updates the gauges that are read from the database and in-memory state.
+    /// Called on every scrape, so the numbers match `admin_stats`.
+    pub async fn refresh(&self, state: &AppState) -> Result<(), sqlx::Error> {
+        let size = state.db.size() as i64;
+        let idle = state.db.num_idle() as i64;
+        self.db_connections.with_label_values(&["idle"]).set(idle);
+        self.db_connections
+            .with_label_values(&["active"])
+            .set(size - idle);
+        self.db_max_connections
+            .set(state.db.options().get_max_connections() as i64);
+
+        if let Ok(nonces) = state.nonces.lock() {
+            self.nonce_store_size.set(nonces.len() as i64);
+        }
+
+        let certificates: Vec<(String, i64)> =
+            sqlx::query_as("SELECT status, COUNT(*) FROM certificates GROUP BY status")
+                .fetch_all(&state.db)
+                .await?;
+        self.certificates.reset();
+        for (status, count) in certificates {
+            self.certificates.with_label_values(&[&status]).set(count);
+        }
+
+        let pools: Vec<(bool, i64)> =
+            sqlx::query_as("SELECT COALESCE(is_active, true), COUNT(*) FROM pools GROUP BY 1")
+                .fetch_all(&state.db)
+                .await?;
+        self.pools.reset();
+        for (active, count) in pools {
+            self.pools
+                .with_label_values(&[if active { "true" } else { "false" }])
+                .set(count);
+        }
+
+        let pending: (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM validator_requests WHERE status = 'pending'")
+                .fetch_one(&state.db)
+                .await?;
+        self.pending_validator_requests.set(pending.0);
+
+        Ok(())
+    }
+
+    /// Prometheus text exposition of every registered metric.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!(error = %err, "failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
@@ -41,6 +41,8 @@ impl FromRequest for AuthUser {
             )
             .map_err(|_| ApiError::Unauthorized)?;
 
+            super::request_log::record_user(&token_data.claims.sub);
+
             Ok(AuthUser {
                 sub: token_data.claims.sub,
                 role: token_data.claims.role,
@@ -1,3 +1,7 @@
 pub mod auth;
+pub mod rate_limit;
+pub mod request_id;
+pub mod request_log;
+pub mod security;
 
 pub use auth::AuthUser;
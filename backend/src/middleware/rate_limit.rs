@@ -0,0 +1,133 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ConnectionInfo, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::errors::ApiError;
+use crate::state::AppState;
+
+/// Routes limited per client IP. Handlers additionally limit per email,
+/// wallet address or, for name challenges, certificate through `limit_key`.
+const LIMITED_PATHS: &[&str] = &[
+    "/auth/login",
+    "/auth/register",
+    "/auth/nonce",
+    "/auth/verify-wallet",
+    "/auth/2fa/verify",
+    "/auth/password/forgot",
+    "/auth/password/reset",
+];
+
+/// Entries are pruned once the map holds this many keys.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Sliding-window request log keyed by arbitrary strings (`ip:1.2.3.4`,
+/// `login:alice@uni.edu`, ...).
+#[derive(Default)]
+pub struct RateLimiter {
+    hits: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a hit for `key`, or returns the number of seconds to wait when
+    /// `max` hits already happened within `window`.
+    pub fn check(&self, key: &str, max: u32, window: Duration) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap_or_else(|e| e.into_inner());
+
+        if hits.len() >= PRUNE_THRESHOLD {
+            hits.retain(|_, log| {
+                log.back()
+                    .is_some_and(|last| now.duration_since(*last) < window)
+            });
+        }
+
+        let log = hits.entry(key.to_string()).or_default();
+        while log
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= window)
+        {
+            log.pop_front();
+        }
+
+        if log.len() >= max as usize {
+            let oldest = log.front().copied().unwrap_or(now);
+            let retry_after = window.saturating_sub(now.duration_since(oldest));
+            return Err(retry_after.as_secs().max(1));
+        }
+
+        log.push_back(now);
+        Ok(())
+    }
+}
+
+/// Limits the number of requests per `key` using the configured window.
+pub fn limit_key(state: &AppState, key: &str) -> Result<(), ApiError> {
+    state
+        .rate_limiter
+        .check(
+            key,
+            state.config.rate_limit_key_max,
+            Duration::from_secs(state.config.rate_limit_window_secs),
+        )
+        .map_err(ApiError::TooManyRequests)
+}
+
+/// Client IP used as rate-limit key. Proxy headers are only honoured when the
+/// deployment says they can be trusted.
+pub fn client_ip(info: &ConnectionInfo, trust_proxy_headers: bool) -> String {
+    let ip = if trust_proxy_headers {
+        info.realip_remote_addr()
+    } else {
+        info.peer_addr()
+    };
+    ip.unwrap_or("unknown").to_string()
+}
+
+pub async fn rate_limit(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if LIMITED_PATHS.contains(&req.path()) {
+        if let Some(state) = req.app_data::<Data<AppState>>() {
+            let key = format!(
+                "ip:{}",
+                client_ip(&req.connection_info(), state.config.trust_proxy_headers)
+            );
+            state
+                .rate_limiter
+                .check(
+                    &key,
+                    state.config.rate_limit_ip_max,
+                    Duration::from_secs(state.config.rate_limit_window_secs),
+                )
+                .map_err(ApiError::TooManyRequests)?;
+        }
+    }
+
+    next.call(req).await
+}
+
+/// Lockout applied after `failed_attempts` wrong passwords: none below the
+/// threshold, then doubling from `base_secs` up to `max_secs`.
+pub fn lockout_duration(
+    failed_attempts: i32,
+    threshold: i32,
+    base_secs: i64,
+    max_secs: i64,
+) -> Option<chrono::Duration> {
+    if threshold <= 0 || failed_attempts < threshold {
+        return None;
+    }
+
+    let doublings = (failed_attempts - threshold).min(30) as u32;
+    let secs = base_secs.saturating_mul(1i64 << doublings).min(max_secs);
+    Some(chrono::Duration::seconds(secs))
+}
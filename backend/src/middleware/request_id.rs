@@ -0,0 +1,61 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, if called from inside `request_id`.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Accepts a client-supplied id when it is short and made of safe characters,
+/// so ids can be correlated across services without log injection.
+fn incoming_id(req: &ServiceRequest) -> Option<String> {
+    let id = req.headers().get(REQUEST_ID_HEADER)?.to_str().ok()?;
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| id.to_string())
+}
+
+fn insert_header(headers: &mut HeaderMap, id: &str) {
+    if let Ok(value) = HeaderValue::from_str(id) {
+        headers.insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+    }
+}
+
+/// Assigns every request an id, makes it available to error responses through
+/// `current()` and echoes it in the `X-Request-Id` response header.
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let id = incoming_id(&req).unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    REQUEST_ID
+        .scope(id.clone(), async move {
+            match next.call(req).await {
+                Ok(mut res) => {
+                    insert_header(res.headers_mut(), &id);
+                    Ok(res)
+                }
+                // Errors from inner middleware are rendered here, while the
+                // request id is still in scope.
+                Err(err) => {
+                    let mut response = err.error_response();
+                    insert_header(response.headers_mut(), &id);
+                    Err(InternalError::from_response(err, response).into())
+                }
+            }
+        })
+        .await
+}
@@ -0,0 +1,68 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use std::time::Instant;
+use tracing::{field, Instrument, Span};
+
+use super::request_id;
+use crate::metrics::UNMATCHED_ROUTE;
+use crate::state::AppState;
+
+/// Records the authenticated subject on the current request span.
+pub fn record_user(sub: &str) {
+    Span::current().record("user_sub", sub);
+}
+
+/// Runs each request inside an `http_request` span, logs one line and updates
+/// the request metrics when it completes. Must be wrapped inside `request_id`
+/// so the id is available.
+pub async fn request_log(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let span = tracing::info_span!(
+        "http_request",
+        method = %req.method(),
+        route = field::Empty,
+        status = field::Empty,
+        latency_ms = field::Empty,
+        user_sub = field::Empty,
+        request_id = request_id::current().unwrap_or_default(),
+    );
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let metrics = req
+        .app_data::<Data<AppState>>()
+        .map(|state| state.metrics.clone());
+    let started = Instant::now();
+
+    let result = next.call(req).instrument(span.clone()).await;
+
+    let elapsed = started.elapsed();
+    let latency_ms = elapsed.as_millis() as u64;
+    let (status, pattern) = match &result {
+        Ok(res) => (res.status(), res.request().match_pattern()),
+        Err(err) => (err.as_response_error().status_code(), None),
+    };
+
+    if let Some(metrics) = metrics {
+        let route = pattern.as_deref().unwrap_or(UNMATCHED_ROUTE);
+        metrics.observe_request(&method, route, status.as_u16(), elapsed.as_secs_f64());
+    }
+
+    span.record("route", pattern.as_deref().unwrap_or(&path));
+    span.record("status", status.as_u16());
+    span.record("latency_ms", latency_ms);
+
+    let _entered = span.enter();
+    if status.is_server_error() {
+        tracing::error!("request failed");
+    } else if status.is_client_error() {
+        tracing::warn!("request rejected");
+    } else {
+        tracing::info!("request completed");
+    }
+
+    result
+}
@@ -0,0 +1,42 @@
+use actix_cors::Cors;
+use actix_web::middleware::DefaultHeaders;
+
+use crate::config::Config;
+
+/// CORS policy for the configured origins. `*` allows any origin; requests
+/// from other origins are rejected.
+pub fn cors(allowed_origins: &[String]) -> Cors {
+    let cors = Cors::default()
+        .allow_any_method()
+        .allow_any_header()
+        .expose_headers([super::request_id::REQUEST_ID_HEADER, "retry-after"])
+        .max_age(3600);
+
+    if allowed_origins.iter().any(|origin| origin == "*") {
+        return cors.allow_any_origin();
+    }
+
+    allowed_origins
+        .iter()
+        .fold(cors, |cors, origin| cors.allowed_origin(origin))
+}
+
+/// Security headers added to every response, each toggled by config.
+pub fn security_headers(config: &Config) -> DefaultHeaders {
+    let mut headers = DefaultHeaders::new();
+
+    if config.content_type_nosniff {
+        headers = headers.add(("X-Content-Type-Options", "nosniff"));
+    }
+    if config.frame_deny {
+        headers = headers.add(("X-Frame-Options", "DENY"));
+    }
+    if config.hsts_enabled {
+        headers = headers.add((
+            "Strict-Transport-Security",
+            format!("max-age={}; includeSubDomains", config.hsts_max_age_secs),
+        ));
+    }
+
+    headers
+}
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use sqlx::FromRow;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -12,6 +13,14 @@ pub struct User {
     pub role: String,
     pub wallet_address: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub email_verified: bool,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+    #[serde(skip_serializing)]
+    pub failed_login_attempts: i32,
+    #[serde(skip_serializing)]
+    pub locked_until: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -28,6 +37,18 @@ pub struct ValidatorRequest {
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Institution {
+    pub id: i32,
+    pub institution_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub logo_url: Option<String>,
+    pub website: Option<String>,
+    pub verified_domains: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Pool {
     pub id: i32,
@@ -38,6 +59,72 @@ pub struct Pool {
     pub tx_hash: Option<String>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
+    pub chain_id: Option<i64>,
+    pub contract_address: Option<String>,
+    pub onchain_pool_id: Option<i64>,
+    pub certificate_template: Option<Json<CertificateTemplate>>,
+    pub privacy: Option<Json<PoolPrivacy>>,
+    /// Institution the pool issues for, set when it is created.
+    pub institution_id: Option<i32>,
+}
+
+/// Which certificate details public verification reveals for a pool.
+/// Recipients can still reveal everything through a share link.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolPrivacy {
+    pub show_recipient_name: bool,
+    pub show_recipient_wallet: bool,
+    pub show_fields: bool,
+    /// Only match when the verifier supplies the recipient's name.
+    pub require_name_challenge: bool,
+}
+
+impl Default for PoolPrivacy {
+    fn default() -> Self {
+        Self {
+            show_recipient_name: true,
+            show_recipient_wallet: true,
+            show_fields: true,
+            require_name_challenge: false,
+        }
+    }
+}
+
+/// Kind of value a template field accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    Text,
+    Number,
+    Date,
+    Boolean,
+    Choice,
+}
+
+/// One structured field of a certificate. `min`/`max` bound numbers, or the
+/// length of text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateField {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+/// Fields a pool's certificates carry, e.g. degree, major, GPA.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CertificateTemplate {
+    pub fields: Vec<TemplateField>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -57,6 +144,16 @@ pub struct Certificate {
     pub minted_at: Option<DateTime<Utc>>,
     pub rejection_reason: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub onchain_pool_id: Option<i64>,
+    pub onchain_request_id: Option<i64>,
+    pub request_tx_hash: Option<String>,
+    pub fields: Option<serde_json::Value>,
+    /// Merkle root over `fields`, fixed when the certificate is submitted so
+    /// it can go into the minted metadata.
+    pub fields_root: Option<String>,
+    /// Per-field salts of the commitment, only handed to the recipient.
+    #[serde(skip_serializing, default)]
+    pub field_salts: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,6 +164,162 @@ pub struct Claims {
     pub exp: usize,
 }
 
+/// Where `GET /certificates/verify/{hash}` looks a certificate up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifySource {
+    #[default]
+    Db,
+    Chain,
+    Both,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct VerifyCertificateQuery {
+    #[serde(default)]
+    pub source: VerifySource,
+    /// Attach a signed verification receipt to a valid answer.
+    #[serde(default)]
+    pub receipt: bool,
+    /// Recipient name, for pools in challenge mode.
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyCredentialRequest {
+    pub jwt: String,
+}
+
+/// Recipient-issued link revealing a certificate's full details.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ShareLink {
+    pub id: i32,
+    pub certificate_id: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateShareLinkRequest {
+    /// Link lifetime; the link does not expire when omitted.
+    pub expires_in_days: Option<i64>,
+}
+
+/// Request to pseudonymize a user's or wallet's personal data, reviewed by an
+/// admin. `result` records what the approved erasure changed.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ErasureRequest {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub wallet: Option<String>,
+    pub reason: Option<String>,
+    pub status: String,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub rejection_reason: Option<String>,
+    pub result: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateErasureRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ErasureDecisionRequest {
+    pub approve: bool,
+    pub rejection_reason: Option<String>,
+}
+
+/// One privileged action. `before`/`after` hold only the columns it changed.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub actor: String,
+    pub actor_type: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub prev_hash: Option<String>,
+    pub entry_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters for `GET /admin/audit`, newest first. `before_id` pages back
+/// from the previous response's `next_before_id`.
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub before_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DisclosureQuery {
+    /// Comma-separated field names to disclose.
+    pub fields: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyDisclosureRequest {
+    pub document_hash: String,
+    pub disclosed: Vec<crate::disclosure::DisclosedField>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Claims of the short-lived token returned by `login` when the account has
+/// two-factor authentication enabled. It can only be exchanged at `/auth/2fa/verify`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: String,
+    pub purpose: String,
+    pub exp: usize,
+}
+
+/// Claims of the token returned with a fields commitment, carrying the salts
+/// drawn for it back to the submission so they are always server-generated.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommitmentClaims {
+    /// Wallet of the certificator the salts were drawn for.
+    pub sub: String,
+    pub purpose: String,
+    pub pool_code: String,
+    pub field_salts: serde_json::Map<String, serde_json::Value>,
+    pub exp: usize,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
@@ -77,9 +330,46 @@ pub struct LoginRequest {
 pub struct LoginResponse {
     pub token: String,
     pub role: String,
+    pub two_factor_setup_required: bool,
     pub user: UserPublic,
 }
 
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorVerifyRequest {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorPolicy {
+    pub required_for_validators: bool,
+    #[serde(default)]
+    pub required_for_admins: bool,
+}
+
+impl TwoFactorPolicy {
+    pub fn required_for(&self, role: &str) -> bool {
+        match role {
+            "validator" => self.required_for_validators,
+            "admin" => self.required_for_admins,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserPublic {
     pub id: i32,
@@ -87,6 +377,8 @@ pub struct UserPublic {
     pub username: String,
     pub role: String,
     pub wallet_address: Option<String>,
+    pub email_verified: bool,
+    pub totp_enabled: bool,
 }
 
 impl From<User> for UserPublic {
@@ -97,6 +389,8 @@ impl From<User> for UserPublic {
             username: u.username,
             role: u.role,
             wallet_address: u.wallet_address,
+            email_verified: u.email_verified,
+            totp_enabled: u.totp_enabled,
         }
     }
 }
@@ -106,11 +400,31 @@ pub struct RegisterRequest {
     pub email: String,
     pub password: String,
     pub username: String,
-    pub institution_name: String,
+    pub institution_name: Option<String>,
     pub institution_id: String,
     pub document_url: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateInstitutionRequest {
+    pub institution_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub logo_url: Option<String>,
+    pub website: Option<String>,
+    #[serde(default)]
+    pub verified_domains: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateInstitutionRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub logo_url: Option<String>,
+    pub website: Option<String>,
+    pub verified_domains: Option<Vec<String>>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct NonceRequest {
     pub address: String,
@@ -133,6 +447,16 @@ pub struct CreatePoolRequest {
     pub name: String,
     pub description: Option<String>,
     pub tx_hash: String,
+    /// Chain the pool was created on. Defaults to the default chain.
+    pub chain_id: Option<u64>,
+}
+
+/// Chain and contract a pool issues on.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainInfo {
+    pub chain_id: i64,
+    pub network: Option<String>,
+    pub contract_address: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -143,8 +467,13 @@ pub struct PoolResponse {
     pub description: Option<String>,
     pub validator_name: String,
     pub institution_name: String,
+    pub institution_id: String,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
+    pub chain: Option<ChainInfo>,
+    pub onchain_pool_id: Option<i64>,
+    pub certificate_template: Option<CertificateTemplate>,
+    pub privacy: PoolPrivacy,
 }
 
 #[derive(Debug, Deserialize)]
@@ -154,6 +483,20 @@ pub struct SubmitCertificateRequest {
     pub certificate_type: String,
     pub document_hash: String,
     pub metadata_uri: Option<String>,
+    /// On-chain id of the pool, checked against the pool when given.
+    pub onchain_pool_id: Option<i64>,
+    /// `submitCertificateRequest` transaction, for on-chain submissions.
+    pub tx_hash: Option<String>,
+    /// Values for the pool's certificate template.
+    pub fields: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Token from `/pools/{code}/certificates/commitment`, for a fields root
+    /// already written into the metadata. Fresh salts are drawn otherwise.
+    pub commitment_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FieldsCommitmentRequest {
+    pub fields: Option<serde_json::Map<String, serde_json::Value>>,
 }
 
 #[derive(Debug, Deserialize)]
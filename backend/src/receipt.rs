@@ -0,0 +1,179 @@
+//! EIP-712 signed verification receipts.
+//!
+//! A receipt is a snapshot of a successful verification that can be checked
+//! offline: anyone holding the backend's signer address can confirm the
+//! fields with [`verify_receipt`] without calling the API.
+
+use ethers_core::abi::{self, Token};
+use ethers_core::k256::ecdsa::signature::Signer;
+use ethers_core::k256::ecdsa::{SigningKey, VerifyingKey};
+use ethers_core::types::transaction::eip712::{EIP712Domain, Eip712};
+use ethers_core::types::{Address, Signature, U256};
+use ethers_core::utils::{keccak256, secret_key_to_address};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub const RECEIPT_DOMAIN_NAME: &str = "Etched";
+pub const RECEIPT_DOMAIN_VERSION: &str = "1";
+
+const RECEIPT_TYPE: &str = "VerificationReceipt(string documentHash,string recipientName,address recipientWallet,string certificateType,string institutionId,string institutionName,uint256 tokenId,uint256 chainId,address contractAddress,uint256 blockNumber,uint256 issuedAt)";
+
+#[derive(Debug, Error)]
+pub enum ReceiptError {
+    #[error("invalid signing key")]
+    InvalidKey,
+
+    #[error("invalid signature: {0}")]
+    Signature(String),
+
+    #[error("receipt was signed by {actual:#x}, expected {expected:#x}")]
+    WrongSigner { expected: Address, actual: Address },
+}
+
+/// Certificate fields attested by a receipt. A `block_number` of 0 means the
+/// mint block could not be looked up when the receipt was issued.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReceipt {
+    pub document_hash: String,
+    pub recipient_name: String,
+    pub recipient_wallet: Address,
+    pub certificate_type: String,
+    pub institution_id: String,
+    pub institution_name: String,
+    pub token_id: u64,
+    pub chain_id: u64,
+    pub contract_address: Address,
+    pub block_number: u64,
+    pub issued_at: u64,
+}
+
+impl Eip712 for VerificationReceipt {
+    type Error = Infallible;
+
+    fn domain(&self) -> Result<EIP712Domain, Self::Error> {
+        Ok(EIP712Domain {
+            name: Some(RECEIPT_DOMAIN_NAME.into()),
+            version: Some(RECEIPT_DOMAIN_VERSION.into()),
+            ..Default::default()
+        })
+    }
+
+    fn type_hash() -> Result<[u8; 32], Self::Error> {
+        Ok(keccak256(RECEIPT_TYPE))
+    }
+
+    fn struct_hash(&self) -> Result<[u8; 32], Self::Error> {
+        let string = |value: &str| Token::FixedBytes(keccak256(value).to_vec());
+        Ok(keccak256(abi::encode(&[
+            Token::FixedBytes(Self::type_hash()?.to_vec()),
+            string(&self.document_hash),
+            string(&self.recipient_name),
+            Token::Address(self.recipient_wallet),
+            string(&self.certificate_type),
+            string(&self.institution_id),
+            string(&self.institution_name),
+            Token::Uint(U256::from(self.token_id)),
+            Token::Uint(U256::from(self.chain_id)),
+            Token::Address(self.contract_address),
+            Token::Uint(U256::from(self.block_number)),
+            Token::Uint(U256::from(self.issued_at)),
+        ])))
+    }
+}
+
+/// A receipt with the backend's signature over its EIP-712 digest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedReceipt {
+    pub receipt: VerificationReceipt,
+    pub signer: Address,
+    /// 65-byte `r || s || v` signature, 0x-prefixed hex.
+    pub signature: String,
+}
+
+/// Address that signed `signed.receipt`, checked against the claimed signer.
+pub fn recover_signer(signed: &SignedReceipt) -> Result<Address, ReceiptError> {
+    let signature = Signature::from_str(&signed.signature)
+        .map_err(|e| ReceiptError::Signature(e.to_string()))?;
+    let digest = signed
+        .receipt
+        .encode_eip712()
+        .unwrap_or_else(|e| match e {});
+    let actual = signature
+        .recover(digest)
+        .map_err(|e| ReceiptError::Signature(e.to_string()))?;
+
+    if actual != signed.signer {
+        return Err(ReceiptError::WrongSigner {
+            expected: signed.signer,
+            actual,
+        });
+    }
+    Ok(actual)
+}
+
+/// Checks a receipt was signed by `trusted`, the published address of the
+/// issuing backend. Needs nothing but the receipt itself.
+pub fn verify_receipt(signed: &SignedReceipt, trusted: Address) -> Result<(), ReceiptError> {
+    let actual = recover_signer(signed)?;
+    if actual != trusted {
+        return Err(ReceiptError::WrongSigner {
+            expected: trusted,
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// The backend's secp256k1 key. It signs verification receipts and the
+/// ES256K proofs of exported credentials.
+pub struct ReceiptSigner {
+    key: SigningKey,
+    address: Address,
+}
+
+impl ReceiptSigner {
+    /// Signer from a hex-encoded secp256k1 private key.
+    pub fn from_hex(key: &str) -> Result<Self, ReceiptError> {
+        let bytes = ethers_core::utils::hex::decode(key.trim().trim_start_matches("0x"))
+            .map_err(|_| ReceiptError::InvalidKey)?;
+        let key = SigningKey::from_slice(&bytes).map_err(|_| ReceiptError::InvalidKey)?;
+        let address = secret_key_to_address(&key);
+        Ok(Self { key, address })
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    /// ES256K signature (SHA-256, low-s `r || s`) over `message`.
+    pub fn sign_es256k(&self, message: &[u8]) -> [u8; 64] {
+        let signature: ethers_core::k256::ecdsa::Signature = self.key.sign(message);
+        signature.to_bytes().into()
+    }
+
+    pub fn sign(&self, receipt: VerificationReceipt) -> Result<SignedReceipt, ReceiptError> {
+        let digest = receipt.encode_eip712().unwrap_or_else(|e| match e {});
+        let (signature, recovery_id) = self
+            .key
+            .sign_prehash_recoverable(&digest)
+            .map_err(|e| ReceiptError::Signature(e.to_string()))?;
+        let bytes = signature.to_bytes();
+        let signature = Signature {
+            r: U256::from_big_endian(&bytes[..32]),
+            s: U256::from_big_endian(&bytes[32..]),
+            v: u64::from(recovery_id.to_byte()) + 27,
+        };
+
+        Ok(SignedReceipt {
+            receipt,
+            signer: self.address,
+            signature: format!("0x{}", signature),
+        })
+    }
+}
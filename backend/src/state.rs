@@ -1,15 +1,25 @@
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
+use crate::chain::Chains;
 use crate::config::Config;
+use crate::mailer::{self, Mailer};
+use crate::metrics::Metrics;
+use crate::middleware::rate_limit::RateLimiter;
+use crate::receipt::ReceiptSigner;
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
     pub db: PgPool,
     pub nonces: std::sync::Arc<Mutex<HashMap<String, String>>>,
+    pub mailer: Arc<dyn Mailer>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub metrics: Arc<Metrics>,
+    pub chains: Chains,
+    pub receipt_signer: Option<Arc<ReceiptSigner>>,
 }
 
 impl AppState {
@@ -20,10 +30,21 @@ impl AppState {
             .await
             .expect("Failed to connect to database");
 
+        let mailer = mailer::from_config(&config).expect("Failed to configure mailer");
+        let chains = Chains::from_config(&config).expect("Failed to configure chain clients");
+        let receipt_signer = config.receipt_signing_key.as_deref().map(|key| {
+            Arc::new(ReceiptSigner::from_hex(key).expect("Failed to load receipt signing key"))
+        });
+
         Self {
             config,
             db,
             nonces: std::sync::Arc::new(Mutex::new(HashMap::new())),
+            mailer,
+            rate_limiter: Arc::new(RateLimiter::new()),
+            metrics: Arc::new(Metrics::new()),
+            chains,
+            receipt_signer,
         }
     }
 }
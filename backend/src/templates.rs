@@ -0,0 +1,143 @@
+//! Validation of pool certificate templates and the structured fields
+//! certificators submit against them.
+
+use chrono::NaiveDate;
+use serde_json::{Map, Value};
+
+use crate::errors::FieldError;
+use crate::models::{CertificateTemplate, FieldType, TemplateField};
+
+pub const MAX_TEMPLATE_FIELDS: usize = 32;
+const MAX_TEXT_LEN: usize = 500;
+
+fn error(field: String, message: &str) -> FieldError {
+    FieldError {
+        field,
+        message: message.into(),
+    }
+}
+
+pub fn validate_template(template: &CertificateTemplate) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if template.fields.len() > MAX_TEMPLATE_FIELDS {
+        errors.push(error(
+            "fields".into(),
+            &format!("at most {} fields are allowed", MAX_TEMPLATE_FIELDS),
+        ));
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    for (i, field) in template.fields.iter().enumerate() {
+        let path = format!("fields[{}]", i);
+        let valid_name = !field.name.is_empty()
+            && field.name.len() <= 64
+            && field
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_name {
+            errors.push(error(
+                format!("{}.name", path),
+                "must be 1-64 lowercase letters, digits or underscores",
+            ));
+        } else if !seen.insert(field.name.as_str()) {
+            errors.push(error(format!("{}.name", path), "is used more than once"));
+        }
+        if field.field_type == FieldType::Choice && field.options.is_empty() {
+            errors.push(error(
+                format!("{}.options", path),
+                "choice fields need at least one option",
+            ));
+        }
+        if let (Some(min), Some(max)) = (field.min, field.max) {
+            if min > max {
+                errors.push(error(format!("{}.min", path), "must not exceed max"));
+            }
+        }
+    }
+    errors
+}
+
+/// Checks submitted values against the template and returns them with text
+/// trimmed. Errors name the offending value as `fields.<name>`.
+pub fn validate_fields(
+    template: &CertificateTemplate,
+    values: Map<String, Value>,
+) -> Result<Map<String, Value>, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    for name in values.keys() {
+        if !template.fields.iter().any(|field| &field.name == name) {
+            errors.push(error(
+                format!("fields.{}", name),
+                "is not part of the template",
+            ));
+        }
+    }
+
+    let mut cleaned = Map::new();
+    for field in &template.fields {
+        let path = format!("fields.{}", field.name);
+        match values.get(&field.name) {
+            None | Some(Value::Null) => {
+                if field.required {
+                    errors.push(error(path, "is required"));
+                }
+            }
+            Some(value) => match check_value(field, value) {
+                Ok(value) => {
+                    cleaned.insert(field.name.clone(), value);
+                }
+                Err(message) => errors.push(error(path, message)),
+            },
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(cleaned)
+    } else {
+        Err(errors)
+    }
+}
+
+fn in_bounds(field: &TemplateField, n: f64) -> bool {
+    field.min.is_none_or(|min| n >= min) && field.max.is_none_or(|max| n <= max)
+}
+
+fn check_value(field: &TemplateField, value: &Value) -> Result<Value, &'static str> {
+    match field.field_type {
+        FieldType::Text => {
+            let text = value.as_str().ok_or("must be text")?.trim();
+            let len = text.chars().count();
+            if len > MAX_TEXT_LEN || !in_bounds(field, len as f64) {
+                return Err("has an invalid length");
+            }
+            if field.required && text.is_empty() {
+                return Err("is required");
+            }
+            Ok(Value::String(text.into()))
+        }
+        FieldType::Number => {
+            let n = value.as_f64().ok_or("must be a number")?;
+            if !in_bounds(field, n) {
+                return Err("is out of range");
+            }
+            Ok(value.clone())
+        }
+        FieldType::Date => {
+            let date = value.as_str().ok_or("must be a date")?.trim();
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| "must be YYYY-MM-DD")?;
+            Ok(Value::String(date.into()))
+        }
+        FieldType::Boolean => value
+            .as_bool()
+            .map(Value::Bool)
+            .ok_or("must be true or false"),
+        FieldType::Choice => {
+            let choice = value.as_str().ok_or("must be text")?.trim();
+            if !field.options.iter().any(|option| option == choice) {
+                return Err("is not one of the allowed options");
+            }
+            Ok(Value::String(choice.into()))
+        }
+    }
+}
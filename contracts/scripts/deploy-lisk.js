@@ -9,6 +9,7 @@ async function main() {
     const contract = await CertificateSBT.deploy();
 
     await contract.waitForDeployment();
+    const deployReceipt = await contract.deploymentTransaction().wait();
     const address = await contract.getAddress();
 
     console.log(`\n✅ Contract deployed to: ${address}`);
@@ -19,6 +20,7 @@ async function main() {
         address: address,
         network: hre.network.name,
         chainId: hre.network.config.chainId,
+        deploymentBlock: deployReceipt.blockNumber,
         deployedAt: new Date().toISOString()
     };
 
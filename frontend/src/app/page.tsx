@@ -3,7 +3,7 @@
 import { useEffect, useMemo, useState, useCallback } from "react";
 import { ethers } from "ethers";
 import * as api from "@/lib/api";
-import { getContract } from "@/lib/contract";
+import { chainId, getContract } from "@/lib/contract";
 import { ToastContainer, useToast } from "@/components/Toast";
 import { uploadToIPFS, getGatewayUrl } from "@/lib/ipfs";
 
@@ -270,7 +270,8 @@ export default function Home() {
       const res = await api.createPool(token, {
         name: formData.pool_name,
         description: formData.pool_description,
-        tx_hash: tx.hash
+        tx_hash: tx.hash,
+        chain_id: chainId || undefined
       });
 
       addToast(`Pool created! Code: ${res.pool.code}`, "success");
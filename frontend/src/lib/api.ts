@@ -133,6 +133,7 @@ export async function createPool(token: string, payload: {
   name: string;
   description?: string;
   tx_hash: string;
+  chain_id?: number;
 }) {
   const res = await fetch(`${apiBase}/pools`, {
     method: "POST",
@@ -180,6 +181,7 @@ export async function submitCertificate(token: string, poolCode: string, payload
   certificate_type: string;
   document_hash: string;
   metadata_uri?: string;
+  fields?: Record<string, string | number | boolean>;
 }) {
   const res = await fetch(`${apiBase}/pools/${poolCode}/certificates`, {
     method: "POST",
@@ -196,6 +198,32 @@ export async function submitCertificate(token: string, poolCode: string, payload
   return res.json();
 }
 
+export type TemplateField = {
+  name: string;
+  label?: string;
+  type: "text" | "number" | "date" | "boolean" | "choice";
+  required?: boolean;
+  options?: string[];
+  min?: number;
+  max?: number;
+};
+
+export async function setPoolTemplate(token: string, poolId: number, fields: TemplateField[]) {
+  const res = await fetch(`${apiBase}/pools/${poolId}/template`, {
+    method: "PUT",
+    headers: {
+      "Content-Type": "application/json",
+      Authorization: `Bearer ${token}`
+    },
+    body: JSON.stringify({ fields })
+  });
+  if (!res.ok) {
+    const err = await res.json().catch(() => ({}));
+    throw new Error(err.message || "Failed to update template");
+  }
+  return res.json();
+}
+
 export async function listPoolCertificates(token: string, poolCode: string, status?: string) {
   const url = status
     ? `${apiBase}/pools/${poolCode}/certificates?status=${status}`
@@ -236,14 +264,77 @@ export async function myCertificates(token: string) {
   return res.json();
 }
 
-export async function verifyCertificate(hash: string) {
-  const res = await fetch(`${apiBase}/certificates/verify/${hash}`);
+export async function verifyCertificate(
+  hash: string,
+  source: "db" | "chain" | "both" = "db",
+  recipientName?: string
+) {
+  const params = new URLSearchParams({ source });
+  if (recipientName) params.set("name", recipientName);
+  const res = await fetch(
+    `${apiBase}/certificates/verify/${hash}?${params}`
+  );
   if (!res.ok) throw new Error("Verification failed");
   return res.json();
 }
 
 
 
+export async function exportCredential(token: string, id: number) {
+  const res = await fetch(`${apiBase}/certificates/${id}/vc`, {
+    headers: { Authorization: `Bearer ${token}` }
+  });
+  if (!res.ok) {
+    const err = await res.json().catch(() => ({}));
+    throw new Error(err.message || "Failed to export credential");
+  }
+  return res.json();
+}
+
+export async function exportMyData(token: string) {
+  const res = await fetch(`${apiBase}/me/export`, {
+    headers: { Authorization: `Bearer ${token}` }
+  });
+  if (!res.ok) {
+    const err = await res.json().catch(() => ({}));
+    throw new Error(err.message || "Failed to export data");
+  }
+  return res.json();
+}
+
+export async function requestErasure(token: string, reason?: string) {
+  const res = await fetch(`${apiBase}/me/erasure`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+      Authorization: `Bearer ${token}`
+    },
+    body: JSON.stringify({ reason })
+  });
+  if (!res.ok) {
+    const err = await res.json().catch(() => ({}));
+    throw new Error(err.message || "Failed to request erasure");
+  }
+  return res.json();
+}
+
+export async function adminAuditEvents(
+  token: string,
+  filters: Record<string, string | number> = {}
+) {
+  const params = new URLSearchParams(
+    Object.entries(filters).map(([key, value]) => [key, String(value)])
+  );
+  const res = await fetch(`${apiBase}/admin/audit?${params}`, {
+    headers: { Authorization: `Bearer ${token}` }
+  });
+  if (!res.ok) {
+    const err = await res.json().catch(() => ({}));
+    throw new Error(err.message || "Failed to load audit log");
+  }
+  return res.json();
+}
+
 export async function publicStats() {
   const res = await fetch(`${apiBase}/stats`);
   if (!res.ok) throw new Error("Failed to load stats");